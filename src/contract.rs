@@ -31,18 +31,65 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
   match msg {
     ExecuteMsg::Submit(req) => execute::submit(deps, env, info, req),
+    ExecuteMsg::SubmitBatch { orders, atomic } => execute::submit_batch(deps, env, info, orders, atomic),
+    ExecuteMsg::SubmitOco { a, b } => execute::submit_oco(deps, env, info, a, b),
+    ExecuteMsg::SubmitBracket {
+      entry,
+      take_profit_price,
+      stop_loss_price,
+    } => execute::submit_bracket(deps, env, info, entry, take_profit_price, stop_loss_price),
+    ExecuteMsg::Receive(msg) => execute::receive(deps, env, info, msg),
+    ExecuteMsg::Deposit {} => execute::deposit(deps, env, info),
+    ExecuteMsg::Cancel {
+      order_id,
+      client_order_id,
+    } => execute::cancel(deps, env, info, order_id, client_order_id),
+    ExecuteMsg::Amend {
+      order_id,
+      client_order_id,
+      new_qty,
+      new_price,
+    } => execute::amend(deps, env, info, order_id, client_order_id, new_qty, new_price),
+    ExecuteMsg::CancelAll {
+      quote,
+      side,
+      price_range,
+      limit,
+      cursor,
+    } => {
+      let filters = execute::CancelAllFilters {
+        quote,
+        side,
+        price_range,
+      };
+      execute::cancel_all(deps, env, info, filters, limit, cursor)
+    },
+    ExecuteMsg::Withdraw {
+      token,
+      amount,
+      recipient,
+    } => execute::withdraw(deps, env, info, token, amount, recipient),
+    ExecuteMsg::WithdrawAll { recipient } => execute::withdraw_all(deps, env, info, recipient),
   }
 }
 
 #[entry_point]
 pub fn query(
   deps: Deps,
-  _env: Env,
+  env: Env,
   msg: QueryMsg,
 ) -> Result<Binary, ContractError> {
   let result = match msg {
     QueryMsg::Select { fields, account } => to_binary(&query::select(deps, fields, account)?),
-    QueryMsg::Orders { account, limit, cursor } => to_binary(&query::orders(deps, account, cursor, limit)?),
+    QueryMsg::Orders { account, limit, cursor } => to_binary(&query::orders(deps, env, account, cursor, limit)?),
+    QueryMsg::Order {
+      account,
+      order_id,
+      client_order_id,
+    } => to_binary(&query::order(deps, env, account, order_id, client_order_id)?),
+    QueryMsg::Depth { quote, limit } => to_binary(&query::depth(deps, env, quote, limit)?),
+    QueryMsg::Dust {} => to_binary(&query::dust(deps)?),
+    QueryMsg::MarketConfig { quote } => to_binary(&query::market_config(deps, quote)?),
   }?;
   Ok(result)
 }
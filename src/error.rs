@@ -26,6 +26,84 @@ pub enum ContractError {
 
   #[error("Cw20InstantiationFailed")]
   Cw20InstantiationFailed,
+
+  #[error("InsufficientFunds")]
+  InsufficientFunds,
+
+  #[error("ExcessFunds")]
+  ExcessFunds,
+
+  #[error("UnexpectedFunds")]
+  UnexpectedFunds,
+
+  #[error("QuoteAmountOverflow")]
+  QuoteAmountOverflow,
+
+  #[error("InsufficientBalance")]
+  InsufficientBalance,
+
+  #[error("OrderNotFound")]
+  OrderNotFound,
+
+  #[error("OrderNotOpen")]
+  OrderNotOpen,
+
+  #[error("InvalidQuantity")]
+  InvalidQuantity,
+
+  #[error("InvalidPrice")]
+  InvalidPrice,
+
+  #[error("InvalidExpiration")]
+  InvalidExpiration,
+
+  #[error("PostOnlyWouldMatch")]
+  PostOnlyWouldMatch,
+
+  #[error("TriggerPriceReached")]
+  TriggerPriceReached,
+
+  #[error("InvalidTrailingOffset")]
+  InvalidTrailingOffset,
+
+  #[error("LastPriceNotFound")]
+  LastPriceNotFound,
+
+  #[error("InvalidOrderGroup")]
+  InvalidOrderGroup,
+
+  #[error("InvalidBatchSize")]
+  InvalidBatchSize,
+
+  #[error("InvalidOrderId")]
+  InvalidOrderId,
+
+  #[error("InvalidClientOrderId")]
+  InvalidClientOrderId,
+
+  #[error("DuplicateClientOrderId")]
+  DuplicateClientOrderId,
+
+  #[error("SlippageExceeded")]
+  SlippageExceeded,
+
+  #[error("InvalidPricePrecision")]
+  InvalidPricePrecision,
+
+  #[error("InvalidMarketConfig")]
+  InvalidMarketConfig,
+
+  #[error("PriceNotOnTick")]
+  PriceNotOnTick,
+
+  #[error("QuantityNotOnLot")]
+  QuantityNotOnLot,
+
+  #[error("QuantityBelowMin")]
+  QuantityBelowMin,
+
+  #[error("NotionalBelowMin")]
+  NotionalBelowMin,
 }
 
 impl From<ContractError> for StdError {
@@ -0,0 +1,155 @@
+use cosmwasm_std::{attr, Decimal256, DepsMut, Env, MessageInfo, Response, Uint128, Uint64};
+
+use crate::{
+  error::ContractError,
+  msg::OrderRequest,
+  state::{
+    decrement_locked_balance, from_price_units, increment_token_balance, load_token_by_id, resolve_order_id,
+    to_price_units, ASKS, BIDS, BOOK_ORDER_IDS, ORDERS,
+  },
+};
+
+use super::{
+  cancel::get_locked_escrow,
+  group::{process_bracket_fills, queue_bracket_fill, resize_group_siblings},
+  submit::{get_next_order_id, place_order_with_id, validate_market_rules},
+};
+
+/// Amend the unmatched qty and/or limit price of an open order. Reducing qty
+/// alone keeps the order's place in the book and releases the excess escrow.
+/// Otherwise, the order is taken out of the book and placed again, which loses
+/// time priority and draws any additional escrow from the owner's balance. The
+/// order keeps its ID, options, owner-assigned ID and group either way.
+pub fn amend(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  maybe_order_id: Option<Uint64>,
+  maybe_client_order_id: Option<String>,
+  maybe_new_qty: Option<Uint128>,
+  maybe_new_price: Option<Decimal256>,
+) -> Result<Response, ContractError> {
+  let order_id = resolve_order_id(deps.storage, &info.sender, maybe_order_id, maybe_client_order_id)?;
+  let mut order = ORDERS
+    .may_load(deps.storage, order_id)?
+    .ok_or(ContractError::OrderNotFound)?;
+
+  if order.owner != info.sender {
+    return Err(ContractError::NotAuthorized {});
+  }
+  if !(order.is_limit_order() && order.is_open()) {
+    return Err(ContractError::OrderNotOpen);
+  }
+
+  let qty_unmatched = order.get_qty_unmatched();
+  let new_qty = maybe_new_qty.unwrap_or(qty_unmatched);
+  let new_price = match maybe_new_price {
+    Some(new_price) => new_price,
+    None => from_price_units(deps.storage, order.quote_token_id, order.limit_price)?,
+  };
+
+  if new_qty.is_zero() {
+    return Err(ContractError::InvalidQuantity);
+  }
+  if new_price.is_zero() {
+    return Err(ContractError::InvalidPrice);
+  }
+
+  // The amended order follows the market's rules, whether it's amended in
+  // place or replaced.
+  let quote = load_token_by_id(deps.storage, order.quote_token_id)?;
+  let tif = order.get_tif();
+  let req = if order.is_buy_side() {
+    OrderRequest::LimitBuy {
+      quote,
+      tif,
+      qty: new_qty,
+      price: new_price,
+      post_only: order.post_only.clone(),
+      display_qty: order.display_qty,
+      min_fill_qty: order.min_fill_qty,
+      all_or_none: order.all_or_none,
+      self_trade_prevention: order.self_trade_prevention.clone(),
+      client_order_id: order.client_order_id.clone(),
+    }
+  } else {
+    OrderRequest::LimitSell {
+      quote,
+      tif,
+      qty: new_qty,
+      price: new_price,
+      post_only: order.post_only.clone(),
+      display_qty: order.display_qty,
+      min_fill_qty: order.min_fill_qty,
+      all_or_none: order.all_or_none,
+      self_trade_prevention: order.self_trade_prevention.clone(),
+      client_order_id: order.client_order_id.clone(),
+    }
+  };
+  validate_market_rules(deps.storage, &req)?;
+
+  let new_price_units = to_price_units(deps.storage, order.quote_token_id, new_price)?;
+  if new_price_units == order.limit_price && new_qty <= qty_unmatched {
+    // Shrink the order in place, keeping its time priority.
+    let qty_delta = qty_unmatched - new_qty;
+    let (token_id, locked_before) = get_locked_escrow(deps.storage, &order)?;
+
+    order.qty_requested -= qty_delta;
+    order.visible_qty = order.visible_qty.map(|qty| qty.min(new_qty));
+    let escrow_delta = locked_before - get_locked_escrow(deps.storage, &order)?.1;
+    ORDERS.save(deps.storage, order_id, &order)?;
+
+    decrement_locked_balance(deps.storage, &order.owner, token_id, escrow_delta)?;
+    increment_token_balance(deps.storage, &order.owner, token_id, escrow_delta)?;
+
+    return Ok(Response::new().add_attributes(vec![
+      attr("action", "amend_order"),
+      attr("order_id", order_id.to_string()),
+      attr("qty", new_qty.to_string()),
+      attr("price", new_price.to_string()),
+    ]));
+  }
+
+  // Take the order out of the book, releasing its escrow to the owner's free
+  // balance, from which the amended order's escrow is then drawn. It's placed
+  // again under its own ID, behind the orders already at its new price.
+  let (token_id, escrow_locked) = get_locked_escrow(deps.storage, &order)?;
+  let map = if order.is_buy_side() { BIDS } else { ASKS };
+  let book_id = order.get_book_id(order_id);
+  map.remove(deps.storage, (order.quote_token_id, order.limit_price.u128(), book_id));
+  BOOK_ORDER_IDS.remove(deps.storage, book_id);
+  decrement_locked_balance(deps.storage, &order.owner, token_id, escrow_locked)?;
+  increment_token_balance(deps.storage, &order.owner, token_id, escrow_locked)?;
+
+  let new_book_id = get_next_order_id(deps.storage)?;
+  let (new_order, refund_msgs, mut events) = place_order_with_id(
+    deps.storage,
+    &env.block,
+    &req,
+    &info.sender,
+    None,
+    order_id,
+    new_book_id,
+  )?;
+
+  // Matching on arrival resizes the rest of the order's group and places the
+  // exits of a bracket entry, as fills of the original order would.
+  if !new_order.qty_matched.is_zero() {
+    resize_group_siblings(deps.storage, order_id, new_qty, new_order.get_qty_unmatched())?;
+    queue_bracket_fill(deps.storage, order_id, new_order.qty_matched)?;
+  }
+  events.extend(process_bracket_fills(deps.storage, &env.block)?);
+
+  Ok(
+    Response::new()
+      .add_messages(refund_msgs)
+      .add_events(events)
+      .add_attributes(vec![
+        attr("action", "amend_order"),
+        attr("order_id", order_id.to_string()),
+        attr("order_status", format!("{:?}", new_order.status)),
+        attr("qty", new_qty.to_string()),
+        attr("price", new_price.to_string()),
+      ]),
+  )
+}
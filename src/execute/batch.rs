@@ -0,0 +1,86 @@
+use cosmwasm_std::{attr, to_binary, DepsMut, Env, Event, MessageInfo, Response, Uint64};
+use cw_lib::models::Token;
+
+use crate::{
+  error::ContractError,
+  models::OrderStatus,
+  msg::{BatchOrderResult, OrderRequest},
+  transaction::StorageTransaction,
+};
+
+use super::{deposit::credit_deposit, submit::process_order_request};
+
+/// Max number of orders in a batch.
+const MAX_BATCH_SIZE: usize = 50;
+
+/// Submit a batch of orders, escrowed from the owner's balance. Any funds sent
+/// with the batch are deposited first. An atomic batch fails entirely if any
+/// order fails. Otherwise, each failed order is discarded on its own. Either
+/// way, the result of each order is returned in the response data.
+pub fn submit_batch(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  reqs: Vec<OrderRequest>,
+  atomic: bool,
+) -> Result<Response, ContractError> {
+  if reqs.is_empty() || reqs.len() > MAX_BATCH_SIZE {
+    return Err(ContractError::InvalidBatchSize);
+  }
+
+  for coin in info.funds.iter() {
+    let token = Token::Native {
+      denom: coin.denom.clone(),
+    };
+    credit_deposit(deps.storage, &info.sender, &token, coin.amount)?;
+  }
+
+  let mut results: Vec<BatchOrderResult> = Vec::with_capacity(reqs.len());
+  let mut events: Vec<Event> = vec![];
+
+  for req in reqs.iter() {
+    if atomic {
+      let (order_id, order, _, order_events) =
+        process_order_request(deps.storage, &env.block, req, &info.sender, None)?;
+      events.extend(order_events);
+      results.push(BatchOrderResult {
+        order_id: Some(Uint64::from(order_id)),
+        status: Some(OrderStatus::from(order.status)),
+        error: None,
+      });
+    } else {
+      let mut tx = StorageTransaction::new(deps.storage);
+      match process_order_request(&mut tx, &env.block, req, &info.sender, None) {
+        Ok((order_id, order, _, order_events)) => {
+          tx.commit();
+          events.extend(order_events);
+          results.push(BatchOrderResult {
+            order_id: Some(Uint64::from(order_id)),
+            status: Some(OrderStatus::from(order.status)),
+            error: None,
+          });
+        },
+        Err(err) => {
+          results.push(BatchOrderResult {
+            order_id: None,
+            status: None,
+            error: Some(err.to_string()),
+          });
+        },
+      }
+    }
+  }
+
+  let error_count = results.iter().filter(|result| result.error.is_some()).count();
+
+  Ok(
+    Response::new()
+      .set_data(to_binary(&results)?)
+      .add_events(events)
+      .add_attributes(vec![
+        attr("action", "submit_batch"),
+        attr("order_count", (results.len() - error_count).to_string()),
+        attr("error_count", error_count.to_string()),
+      ]),
+  )
+}
@@ -0,0 +1,227 @@
+use cosmwasm_std::{attr, Decimal256, DepsMut, Env, MessageInfo, Response, Storage, Uint128, Uint64};
+use cw_lib::models::Token;
+use cw_storage_plus::Bound;
+use std::marker::PhantomData;
+
+use crate::{
+  error::ContractError,
+  models::{Order, OrderId, OrderSide, OrderStatus},
+  state::{
+    decrement_locked_balance, from_price_units, increment_token_balance, load_price_scale, load_token_id,
+    resolve_order_id, ACCOUNT_ORDER_IDS, ASKS, BASE_TOKEN_ID, BIDS, BOOK_ORDER_IDS, BRACKETS, BRACKET_FILLS,
+    BUY_TRIGGERS, ORDERS, SELL_TRIGGERS,
+  },
+  utils::mul_price,
+};
+
+use super::{
+  group::{cancel_group_siblings, is_contingent_order},
+  submit::remove_trailing_stop,
+};
+
+pub fn cancel(
+  deps: DepsMut,
+  _env: Env,
+  info: MessageInfo,
+  maybe_order_id: Option<Uint64>,
+  maybe_client_order_id: Option<String>,
+) -> Result<Response, ContractError> {
+  let order_id = resolve_order_id(deps.storage, &info.sender, maybe_order_id, maybe_client_order_id)?;
+  let mut order = ORDERS
+    .may_load(deps.storage, order_id)?
+    .ok_or(ContractError::OrderNotFound)?;
+
+  if order.owner != info.sender {
+    return Err(ContractError::NotAuthorized {});
+  }
+
+  let refund_amount = cancel_order(deps.storage, order_id, &mut order)?;
+
+  Ok(Response::new().add_attributes(vec![
+    attr("action", "cancel_order"),
+    attr("order_id", order_id.to_string()),
+    attr("qty_canceled", order.get_qty_unmatched().to_string()),
+    attr("refund", refund_amount.to_string()),
+  ]))
+}
+
+/// Filters selecting which of the sender's open orders to cancel.
+#[derive(Default)]
+pub struct CancelAllFilters {
+  pub quote: Option<Token>,
+  pub side: Option<OrderSide>,
+  pub price_range: Option<(Decimal256, Decimal256)>,
+}
+
+pub fn cancel_all(
+  deps: DepsMut,
+  _env: Env,
+  info: MessageInfo,
+  filters: CancelAllFilters,
+  maybe_limit: Option<u8>,
+  maybe_cursor: Option<Uint64>,
+) -> Result<Response, ContractError> {
+  let limit = maybe_limit.unwrap_or(50).clamp(1, 100) as usize;
+  let maybe_quote_token_id = if let Some(quote) = &filters.quote {
+    Some(load_token_id(deps.storage, quote)?)
+  } else {
+    None
+  };
+
+  let start_bound = maybe_cursor.map(|cursor| Bound::Exclusive((cursor.u64(), PhantomData)));
+
+  // Scan up to `limit` of the sender's orders after the cursor, collecting the
+  // open ones that match the given filters. If the scan stops at the limit,
+  // the last order ID scanned is returned as the cursor to continue from.
+  let mut orders: Vec<(OrderId, Order)> = Vec::with_capacity(limit);
+  let mut scan_count: usize = 0;
+  let mut last_scanned_order_id: Option<OrderId> = None;
+  for result in ACCOUNT_ORDER_IDS
+    .prefix(&info.sender)
+    .keys(deps.storage, None, start_bound, cosmwasm_std::Order::Descending)
+    .take(limit)
+  {
+    let order_id = result?;
+    scan_count += 1;
+    last_scanned_order_id = Some(order_id);
+    let order = ORDERS.load(deps.storage, order_id)?;
+    if !(order.is_open() && (order.is_limit_order() || order.is_stop_order())) {
+      continue;
+    }
+    if let Some(quote_token_id) = maybe_quote_token_id {
+      if order.quote_token_id != quote_token_id {
+        continue;
+      }
+    }
+    if let Some(side) = &filters.side {
+      if order.side != side.u8() {
+        continue;
+      }
+    }
+    // Pending stop orders are filtered by their trigger price, since stop-market
+    // and trailing stop orders have no limit price.
+    if let Some((min_price, max_price)) = filters.price_range {
+      let order_price = if order.is_stop_order() {
+        order.trigger_price.unwrap_or_default()
+      } else {
+        order.limit_price
+      };
+      let price = from_price_units(deps.storage, order.quote_token_id, order_price)?;
+      if price < min_price || price > max_price {
+        continue;
+      }
+    }
+    orders.push((order_id, order));
+  }
+
+  let mut order_ids: Vec<String> = Vec::with_capacity(orders.len());
+  for (order_id, _) in orders {
+    // Orders in a group may already be canceled along with an earlier order.
+    let mut order = ORDERS.load(deps.storage, order_id)?;
+    if !order.is_open() {
+      continue;
+    }
+    cancel_order(deps.storage, order_id, &mut order)?;
+    order_ids.push(order_id.to_string());
+  }
+
+  let mut resp = Response::new().add_attributes(vec![
+    attr("action", "cancel_all"),
+    attr("canceled_count", order_ids.len().to_string()),
+    attr("order_ids", order_ids.join(",")),
+  ]);
+  if scan_count == limit {
+    if let Some(order_id) = last_scanned_order_id {
+      resp = resp.add_attribute("cursor", order_id.to_string());
+    }
+  }
+
+  Ok(resp)
+}
+
+/// Cancel an open order along with the other orders in its group, returning
+/// the amount released by the order itself.
+pub(crate) fn cancel_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &mut Order,
+) -> Result<Uint128, ContractError> {
+  let refund_amount = close_order(storage, order_id, order, OrderStatus::Canceled)?;
+  cancel_group_siblings(storage, order_id)?;
+  Ok(refund_amount)
+}
+
+/// Remove an open order from the book, or a pending stop order from the
+/// trigger book, mark it with the given final status, and release the escrow
+/// backing its unmatched qty to the owner's free balance. Returns the amount
+/// released.
+pub(crate) fn close_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &mut Order,
+  status: OrderStatus,
+) -> Result<Uint128, ContractError> {
+  if !(order.is_open() && (order.is_limit_order() || order.is_stop_order())) {
+    return Err(ContractError::OrderNotOpen);
+  }
+
+  // Stop orders in a group have no escrow locked until they're triggered.
+  let (token_id, refund_amount) = if is_contingent_order(storage, order_id, order) {
+    (BASE_TOKEN_ID, Uint128::zero())
+  } else {
+    get_locked_escrow(storage, order)?
+  };
+
+  if order.is_stop_order() {
+    let map = if order.is_buy_side() {
+      BUY_TRIGGERS
+    } else {
+      SELL_TRIGGERS
+    };
+    let trigger_price = order.trigger_price.unwrap_or_default();
+    map.remove(storage, (order.quote_token_id, trigger_price.u128(), order_id));
+    remove_trailing_stop(storage, order_id, order);
+  } else {
+    let map = if order.is_buy_side() { BIDS } else { ASKS };
+    let book_id = order.get_book_id(order_id);
+    map.remove(storage, (order.quote_token_id, order.limit_price.u128(), book_id));
+    BOOK_ORDER_IDS.remove(storage, book_id);
+  }
+
+  // A closed bracket entry places no more exits, except for fills still queued.
+  if !BRACKET_FILLS.has(storage, order_id) {
+    BRACKETS.remove(storage, order_id);
+  }
+
+  order.status = status.into();
+  ORDERS.save(storage, order_id, order)?;
+
+  if !refund_amount.is_zero() {
+    decrement_locked_balance(storage, &order.owner, token_id, refund_amount)?;
+    increment_token_balance(storage, &order.owner, token_id, refund_amount)?;
+  }
+
+  Ok(refund_amount)
+}
+
+/// Get the token ID and amount of escrow locked by an open order, which is
+/// quote for buy orders and base for sell orders. A buy order's locked quote
+/// is the difference between the rounded quote amounts of its requested and
+/// matched qty, so that its fills never leave any rounding remainder locked.
+pub(crate) fn get_locked_escrow(
+  storage: &dyn Storage,
+  order: &Order,
+) -> Result<(u32, Uint128), ContractError> {
+  if order.is_buy_side() {
+    if order.is_stop_market_order() {
+      Ok((order.quote_token_id, order.balance))
+    } else {
+      let price_scale = load_price_scale(storage, order.quote_token_id)?;
+      let quote_requested = mul_price(order.qty_requested, order.limit_price, price_scale);
+      let quote_matched = mul_price(order.qty_matched, order.limit_price, price_scale);
+      Ok((order.quote_token_id, quote_requested - quote_matched))
+    }
+  } else {
+    Ok((BASE_TOKEN_ID, order.get_qty_unmatched()))
+  }
+}
@@ -0,0 +1,42 @@
+use cosmwasm_std::{attr, Addr, Attribute, DepsMut, Env, MessageInfo, Response, Storage, Uint128};
+use cw_lib::models::Token;
+
+use crate::{
+  error::ContractError,
+  state::{increment_token_balance, load_token_id},
+};
+
+pub fn deposit(
+  deps: DepsMut,
+  _env: Env,
+  info: MessageInfo,
+) -> Result<Response, ContractError> {
+  if info.funds.is_empty() {
+    return Err(ContractError::InsufficientFunds);
+  }
+
+  let mut attrs: Vec<Attribute> = vec![attr("action", "deposit"), attr("owner", info.sender.to_string())];
+
+  for coin in info.funds.iter() {
+    let token = Token::Native {
+      denom: coin.denom.clone(),
+    };
+    credit_deposit(deps.storage, &info.sender, &token, coin.amount)?;
+    attrs.push(attr("token", token.get_key()));
+    attrs.push(attr("amount", coin.amount.to_string()));
+  }
+
+  Ok(Response::new().add_attributes(attrs))
+}
+
+/// Add deposited funds to the owner's internal balance, provided the token is
+/// traded in this contract.
+pub(crate) fn credit_deposit(
+  storage: &mut dyn Storage,
+  owner: &Addr,
+  token: &Token,
+  amount: Uint128,
+) -> Result<(), ContractError> {
+  let token_id = load_token_id(storage, token)?;
+  increment_token_balance(storage, owner, token_id, amount)
+}
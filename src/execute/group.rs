@@ -0,0 +1,538 @@
+use cosmwasm_std::{attr, Addr, BlockInfo, Decimal256, DepsMut, Env, Event, MessageInfo, Response, Storage, Uint128};
+
+use crate::{
+  error::ContractError,
+  models::{Bracket, Order, OrderId, OrderSide, OrderStatus, TimeInForce},
+  msg::OrderRequest,
+  state::{
+    decrement_locked_balance, decrement_token_balance, from_price_units, increment_locked_balance,
+    increment_token_balance, load_market_config, load_price_scale, load_token_by_id, load_token_id, to_price_units,
+    BASE_TOKEN_ID, BRACKETS, BRACKET_FILLS, GROUP_ORDER_IDS, ORDERS, ORDER_GROUP_IDS, TOKEN_BALANCES,
+  },
+  utils::{checked_mul_price, mul_price},
+};
+
+use super::{
+  cancel::{close_order, get_locked_escrow},
+  submit::{
+    get_native_funds, place_order, process_order_request, process_triggers, round_to_lot, save_contingent_stop_order,
+    validate_market_rules, validate_price,
+  },
+};
+
+/// Max number of brackets whose exits are placed after any one order.
+const MAX_BRACKET_FILLS: usize = 10;
+
+/// Submit two orders as a one-cancels-other group. When either order fills or
+/// is canceled, the other is canceled, and when either partially fills, the
+/// other is resized by the same proportion. Each order must be a resting limit order or
+/// a stop order. Orders in a group are funded from the owner's balance, and
+/// stop orders draw their escrow only once triggered.
+pub fn submit_oco(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  a: OrderRequest,
+  b: OrderRequest,
+) -> Result<Response, ContractError> {
+  if !info.funds.is_empty() {
+    return Err(ContractError::UnexpectedFunds);
+  }
+  validate_group_order_request(&a)?;
+  validate_group_order_request(&b)?;
+
+  let storage = deps.storage;
+  let block = &env.block;
+  let owner = &info.sender;
+
+  // The group ID is the ID of its first order.
+  let (a_order_id, a_order, mut events) = place_group_order(storage, block, owner, &a)?;
+  let group_id = a_order_id;
+  link_group_order(storage, group_id, a_order_id)?;
+
+  let mut order_ids = vec![a_order_id.to_string()];
+
+  // If the first order filled on arrival, the second is never placed.
+  if a_order.is_open() {
+    let (b_order_id, b_order, b_events) = place_group_order(storage, block, owner, &b)?;
+    events.extend(b_events);
+    link_group_order(storage, group_id, b_order_id)?;
+    order_ids.push(b_order_id.to_string());
+
+    // Resize each order by however much the other matched on arrival.
+    for (order_id, order) in [(a_order_id, &a_order), (b_order_id, &b_order)] {
+      if order.is_limit_order() && !order.qty_matched.is_zero() {
+        resize_group_siblings(storage, order_id, order.qty_requested, order.get_qty_unmatched())?;
+      }
+    }
+
+    // Stop orders whose trigger price was already reached trigger now.
+    events.extend(process_triggers(storage, block, a_order.quote_token_id)?);
+    events.extend(process_triggers(storage, block, b_order.quote_token_id)?);
+    events.extend(process_bracket_fills(storage, block)?);
+  }
+
+  Ok(Response::new().add_events(events).add_attributes(vec![
+    attr("action", "submit_oco"),
+    attr("group_id", group_id.to_string()),
+    attr("order_ids", order_ids.join(",")),
+  ]))
+}
+
+/// Submit an entry order with take-profit and stop-loss prices. As the entry
+/// fills, exit orders for the filled qty are placed on the opposite side of
+/// the book as a one-cancels-other group: a limit order at the take-profit
+/// price and a stop-market order triggered at the stop-loss price. Exits are
+/// funded from the owner's balance, which is credited by the entry's fills.
+pub fn submit_bracket(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  entry: OrderRequest,
+  take_profit_price: Decimal256,
+  stop_loss_price: Decimal256,
+) -> Result<Response, ContractError> {
+  let is_buy_entry = match &entry {
+    OrderRequest::MarketBuy { .. } | OrderRequest::MarketBuyQty { .. } | OrderRequest::LimitBuy { .. } => true,
+    OrderRequest::MarketSell { .. } | OrderRequest::MarketSellQuote { .. } | OrderRequest::LimitSell { .. } => false,
+    _ => return Err(ContractError::InvalidOrderGroup),
+  };
+
+  // Exit prices follow the same rules as the entry's market. Long entries take
+  // profit above the stop loss, and short entries below it.
+  let quote_token_id = load_token_id(deps.storage, entry.get_quote())?;
+  let market = load_market_config(deps.storage, quote_token_id)?;
+  let take_profit_price = to_price_units(deps.storage, quote_token_id, take_profit_price)?;
+  let stop_loss_price = to_price_units(deps.storage, quote_token_id, stop_loss_price)?;
+  validate_price(&market, take_profit_price)?;
+  validate_price(&market, stop_loss_price)?;
+  if (is_buy_entry && take_profit_price <= stop_loss_price) || (!is_buy_entry && take_profit_price >= stop_loss_price) {
+    return Err(ContractError::InvalidPrice);
+  }
+
+  let funds = get_native_funds(&info.funds)?;
+  let (order_id, order, refund_msgs, mut events) =
+    process_order_request(deps.storage, &env.block, &entry, &info.sender, funds)?;
+
+  let bracket = Bracket {
+    take_profit_price,
+    stop_loss_price,
+    take_profit_order_id: None,
+    stop_loss_order_id: None,
+  };
+  BRACKETS.save(deps.storage, order_id, &bracket)?;
+
+  if order.qty_matched.is_zero() {
+    if !order.is_open() {
+      BRACKETS.remove(deps.storage, order_id);
+    }
+  } else {
+    events.extend(fill_bracket(
+      deps.storage,
+      &env.block,
+      order_id,
+      &order,
+      order.qty_matched,
+    )?);
+    events.extend(process_bracket_fills(deps.storage, &env.block)?);
+  }
+
+  Ok(
+    Response::new()
+      .add_messages(refund_msgs)
+      .add_events(events)
+      .add_attributes(vec![
+        attr("action", "submit_bracket"),
+        attr("order_id", order_id.to_string()),
+        attr("order_status", format!("{:?}", order.status)),
+      ]),
+  )
+}
+
+/// Queue the exits of a bracket for qty newly filled by its entry as a maker,
+/// adding to any fill already queued. Orders that aren't bracket entries are
+/// ignored.
+pub(crate) fn queue_bracket_fill(
+  storage: &mut dyn Storage,
+  entry_order_id: OrderId,
+  qty_filled: Uint128,
+) -> Result<(), ContractError> {
+  if qty_filled.is_zero() || !BRACKETS.has(storage, entry_order_id) {
+    return Ok(());
+  }
+  BRACKET_FILLS.update(storage, entry_order_id, |maybe_qty| -> Result<_, ContractError> {
+    Ok(maybe_qty.unwrap_or_default() + qty_filled)
+  })?;
+  Ok(())
+}
+
+/// Place the exits of bracket entries filled as makers, which are queued while
+/// matches are settled rather than placed there, since exits can match and
+/// fill other brackets' entries in turn. Fills beyond the max number of
+/// brackets stay queued for later orders to place.
+pub(crate) fn process_bracket_fills(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+) -> Result<Vec<Event>, ContractError> {
+  let mut events: Vec<Event> = vec![];
+  for _ in 0..MAX_BRACKET_FILLS {
+    if let Some((entry_order_id, qty_filled)) = BRACKET_FILLS.first(storage)? {
+      BRACKET_FILLS.remove(storage, entry_order_id);
+      let entry_order = ORDERS.load(storage, entry_order_id)?;
+      events.extend(fill_bracket(storage, block, entry_order_id, &entry_order, qty_filled)?);
+    } else {
+      break;
+    }
+  }
+  Ok(events)
+}
+
+/// Place or grow the exits of a bracket for qty newly filled by its entry. The
+/// exits are capped at what the owner's balance can fund, so a fill is never
+/// blocked by its exits. Returns the events of any stop orders triggered by
+/// placing the exits.
+pub(crate) fn fill_bracket(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  entry_order_id: OrderId,
+  entry_order: &Order,
+  qty_filled: Uint128,
+) -> Result<Vec<Event>, ContractError> {
+  let mut events: Vec<Event> = vec![];
+  let mut bracket = if let Some(bracket) = BRACKETS.may_load(storage, entry_order_id)? {
+    bracket
+  } else {
+    return Ok(events);
+  };
+
+  let is_buy_entry = entry_order.is_buy_side();
+  let owner = &entry_order.owner;
+  let quote_token_id = entry_order.quote_token_id;
+  let price_scale = load_price_scale(storage, quote_token_id)?;
+
+  // Sell exits escrow the base qty bought, while buy exits escrow the quote
+  // needed to buy back the qty sold at the take-profit price. Exits are sized
+  // in whole lots.
+  let qty = if is_buy_entry {
+    let balance = load_free_balance(storage, owner, BASE_TOKEN_ID)?;
+    qty_filled.min(balance)
+  } else {
+    let balance = load_free_balance(storage, owner, quote_token_id)?;
+    let qty_affordable = balance
+      .checked_multiply_ratio(price_scale, bracket.take_profit_price)
+      .unwrap_or(Uint128::MAX);
+    qty_filled.min(qty_affordable)
+  };
+  let qty = round_to_lot(qty, load_market_config(storage, quote_token_id)?.lot_size);
+
+  if !qty.is_zero() {
+    // Grow whichever exits are still open. If neither is, as when an earlier
+    // pair of exits has already closed out, place a new pair.
+    let mut is_grown = false;
+    for order_id in [bracket.take_profit_order_id, bracket.stop_loss_order_id]
+      .into_iter()
+      .flatten()
+    {
+      let mut order = ORDERS.load(storage, order_id)?;
+      if order.is_open() {
+        grow_exit_order(storage, order_id, &mut order, qty, bracket.stop_loss_price, price_scale)?;
+        is_grown = true;
+      }
+    }
+
+    if !is_grown {
+      let quote = load_token_by_id(storage, quote_token_id)?;
+      let take_profit_price = from_price_units(storage, quote_token_id, bracket.take_profit_price)?;
+      let stop_loss_price = from_price_units(storage, quote_token_id, bracket.stop_loss_price)?;
+      let (take_profit, stop_loss) = if is_buy_entry {
+        (
+          OrderRequest::LimitSell {
+            quote: quote.clone(),
+            qty,
+            price: take_profit_price,
+            tif: TimeInForce::Gtc,
+            post_only: None,
+            display_qty: None,
+            min_fill_qty: None,
+            all_or_none: None,
+            self_trade_prevention: None,
+            client_order_id: None,
+          },
+          OrderRequest::StopMarket {
+            quote,
+            side: OrderSide::Sell,
+            trigger_price: stop_loss_price,
+            amount: qty,
+            tif: TimeInForce::Ioc,
+            client_order_id: None,
+          },
+        )
+      } else {
+        (
+          OrderRequest::LimitBuy {
+            quote: quote.clone(),
+            qty,
+            price: take_profit_price,
+            tif: TimeInForce::Gtc,
+            post_only: None,
+            display_qty: None,
+            min_fill_qty: None,
+            all_or_none: None,
+            self_trade_prevention: None,
+            client_order_id: None,
+          },
+          OrderRequest::StopMarket {
+            quote,
+            side: OrderSide::Buy,
+            trigger_price: stop_loss_price,
+            amount: checked_mul_price(qty, bracket.stop_loss_price, price_scale)?,
+            tif: TimeInForce::Ioc,
+            client_order_id: None,
+          },
+        )
+      };
+
+      let (take_profit_order_id, take_profit_order, _, take_profit_events) =
+        place_order(storage, block, &take_profit, owner, None)?;
+      events.extend(take_profit_events);
+      link_group_order(storage, entry_order_id, take_profit_order_id)?;
+
+      let (stop_loss_order_id, _) = save_contingent_stop_order(storage, block, owner, &stop_loss)?;
+      link_group_order(storage, entry_order_id, stop_loss_order_id)?;
+
+      // The take-profit order may have matched on arrival.
+      if !take_profit_order.qty_matched.is_zero() {
+        resize_group_siblings(
+          storage,
+          take_profit_order_id,
+          take_profit_order.qty_requested,
+          take_profit_order.get_qty_unmatched(),
+        )?;
+      }
+
+      bracket.take_profit_order_id = Some(take_profit_order_id);
+      bracket.stop_loss_order_id = Some(stop_loss_order_id);
+
+      events.extend(process_triggers(storage, block, quote_token_id)?);
+    }
+  }
+
+  if entry_order.is_open() {
+    BRACKETS.save(storage, entry_order_id, &bracket)?;
+  } else {
+    BRACKETS.remove(storage, entry_order_id);
+  }
+
+  Ok(events)
+}
+
+/// Resize the other open orders in an order's group after it matches, scaling
+/// the unmatched size of each by the same proportion as the matched order's.
+/// Orders sized down to nothing, including all siblings of a filled order, are
+/// canceled.
+pub(crate) fn resize_group_siblings(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  qty_before: Uint128,
+  qty_after: Uint128,
+) -> Result<(), ContractError> {
+  for (sibling_id, mut sibling) in load_open_group_siblings(storage, order_id)? {
+    resize_order(storage, sibling_id, &mut sibling, qty_before, qty_after)?;
+  }
+  Ok(())
+}
+
+/// Cancel the other open orders in an order's group.
+pub(crate) fn cancel_group_siblings(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+) -> Result<(), ContractError> {
+  for (sibling_id, mut sibling) in load_open_group_siblings(storage, order_id)? {
+    close_order(storage, sibling_id, &mut sibling, OrderStatus::Canceled)?;
+  }
+  Ok(())
+}
+
+/// Is the order a stop order in a group, with no escrow locked until it's
+/// triggered?
+pub(crate) fn is_contingent_order(
+  storage: &dyn Storage,
+  order_id: OrderId,
+  order: &Order,
+) -> bool {
+  order.is_stop_order() && ORDER_GROUP_IDS.has(storage, order_id)
+}
+
+pub(crate) fn unlink_group_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+) -> Result<(), ContractError> {
+  if let Some(group_id) = ORDER_GROUP_IDS.may_load(storage, order_id)? {
+    ORDER_GROUP_IDS.remove(storage, order_id);
+    GROUP_ORDER_IDS.remove(storage, (group_id, order_id));
+  }
+  Ok(())
+}
+
+fn link_group_order(
+  storage: &mut dyn Storage,
+  group_id: OrderId,
+  order_id: OrderId,
+) -> Result<(), ContractError> {
+  ORDER_GROUP_IDS.save(storage, order_id, &group_id)?;
+  GROUP_ORDER_IDS.save(storage, (group_id, order_id), &1)?;
+  Ok(())
+}
+
+fn validate_group_order_request(req: &OrderRequest) -> Result<(), ContractError> {
+  match req {
+    OrderRequest::LimitBuy { tif, .. } | OrderRequest::LimitSell { tif, .. } => {
+      if !tif.is_resting() {
+        return Err(ContractError::TimeInForceNotAllowed);
+      }
+      Ok(())
+    },
+    OrderRequest::StopMarket { .. } | OrderRequest::StopLimit { .. } | OrderRequest::TrailingStop { .. } => Ok(()),
+    _ => Err(ContractError::InvalidOrderGroup),
+  }
+}
+
+fn place_group_order(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  owner: &Addr,
+  req: &OrderRequest,
+) -> Result<(OrderId, Order, Vec<Event>), ContractError> {
+  match req {
+    OrderRequest::StopMarket { .. } | OrderRequest::StopLimit { .. } | OrderRequest::TrailingStop { .. } => {
+      validate_market_rules(storage, req)?;
+      let (order_id, order) = save_contingent_stop_order(storage, block, owner, req)?;
+      Ok((order_id, order, vec![]))
+    },
+    _ => {
+      let (order_id, order, _, events) = process_order_request(storage, block, req, owner, None)?;
+      Ok((order_id, order, events))
+    },
+  }
+}
+
+fn load_open_group_siblings(
+  storage: &dyn Storage,
+  order_id: OrderId,
+) -> Result<Vec<(OrderId, Order)>, ContractError> {
+  let group_id = if let Some(group_id) = ORDER_GROUP_IDS.may_load(storage, order_id)? {
+    group_id
+  } else {
+    return Ok(vec![]);
+  };
+  let mut siblings: Vec<(OrderId, Order)> = Vec::with_capacity(2);
+  for result in GROUP_ORDER_IDS
+    .prefix(group_id)
+    .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+  {
+    let sibling_id = result?;
+    if sibling_id == order_id {
+      continue;
+    }
+    let sibling = ORDERS.load(storage, sibling_id)?;
+    if sibling.is_open() {
+      siblings.push((sibling_id, sibling));
+    }
+  }
+  Ok(siblings)
+}
+
+fn load_free_balance(
+  storage: &dyn Storage,
+  owner: &Addr,
+  token_id: u32,
+) -> Result<Uint128, ContractError> {
+  Ok(TOKEN_BALANCES.may_load(storage, (owner, token_id))?.unwrap_or_default())
+}
+
+/// Scale the unmatched size of an open order in a group by qty_after /
+/// qty_before, rounded down to whole lots, canceling it if nothing remains. A
+/// limit order releases the escrow it no longer needs.
+fn resize_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &mut Order,
+  qty_before: Uint128,
+  qty_after: Uint128,
+) -> Result<(), ContractError> {
+  // Stop-market buys are sized by the quote balance they spend.
+  if order.is_stop_market_order() && order.is_buy_side() {
+    let balance = order.balance.multiply_ratio(qty_after, qty_before);
+    if balance.is_zero() {
+      close_order(storage, order_id, order, OrderStatus::Canceled)?;
+      return Ok(());
+    }
+    order.balance = balance;
+    order.funds = balance;
+    ORDERS.save(storage, order_id, order)?;
+    return Ok(());
+  }
+
+  let qty_unmatched = order.get_qty_unmatched();
+  let lot_size = load_market_config(storage, order.quote_token_id)?.lot_size;
+  let new_qty_unmatched = round_to_lot(qty_unmatched.multiply_ratio(qty_after, qty_before), lot_size);
+  if new_qty_unmatched.is_zero() {
+    close_order(storage, order_id, order, OrderStatus::Canceled)?;
+    return Ok(());
+  }
+
+  let qty_delta = qty_unmatched - new_qty_unmatched;
+  let (token_id, locked_before) = get_locked_escrow(storage, order)?;
+  order.qty_requested -= qty_delta;
+  order.visible_qty = order.visible_qty.map(|qty| qty.min(new_qty_unmatched));
+  if order.is_stop_order() {
+    if order.is_buy_side() {
+      let price_scale = load_price_scale(storage, order.quote_token_id)?;
+      order.balance = mul_price(order.qty_requested, order.limit_price, price_scale);
+      order.funds = order.balance;
+    }
+  } else {
+    let escrow_delta = locked_before - get_locked_escrow(storage, order)?.1;
+    decrement_locked_balance(storage, &order.owner, token_id, escrow_delta)?;
+    increment_token_balance(storage, &order.owner, token_id, escrow_delta)?;
+  }
+
+  ORDERS.save(storage, order_id, order)?;
+  Ok(())
+}
+
+/// Grow an open bracket exit order by the given qty. A take-profit limit order
+/// draws the additional escrow from the owner's balance, while a stop-loss
+/// order draws its escrow only once triggered.
+fn grow_exit_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &mut Order,
+  qty: Uint128,
+  stop_loss_price: Uint128,
+  price_scale: Uint128,
+) -> Result<(), ContractError> {
+  if order.is_stop_order() {
+    if order.is_buy_side() {
+      order.balance = order
+        .balance
+        .checked_add(checked_mul_price(qty, stop_loss_price, price_scale)?)
+        .map_err(|_| ContractError::QuoteAmountOverflow)?;
+      order.funds = order.balance;
+    } else {
+      order.qty_requested += qty;
+    }
+  } else {
+    let (token_id, locked_before) = get_locked_escrow(storage, order)?;
+    order.qty_requested += qty;
+    let escrow_amount = get_locked_escrow(storage, order)?.1 - locked_before;
+    decrement_token_balance(storage, &order.owner, token_id, escrow_amount)?;
+    increment_locked_balance(storage, &order.owner, token_id, escrow_amount)?;
+    if order.is_buy_side() {
+      order.balance += escrow_amount;
+      order.funds += escrow_amount;
+    }
+  }
+  ORDERS.save(storage, order_id, order)?;
+  Ok(())
+}
@@ -1,3 +1,17 @@
+mod amend;
+mod batch;
+mod cancel;
+mod deposit;
+mod group;
+mod receive;
 mod submit;
+mod withdraw;
 
+pub use amend::amend;
+pub use batch::submit_batch;
+pub use cancel::{cancel, cancel_all, CancelAllFilters};
+pub use deposit::deposit;
+pub use group::{submit_bracket, submit_oco};
+pub use receive::receive;
 pub use submit::submit;
+pub use withdraw::{withdraw, withdraw_all};
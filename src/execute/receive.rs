@@ -0,0 +1,58 @@
+use cosmwasm_std::{attr, from_binary, DepsMut, Env, MessageInfo, Response};
+use cw20::Cw20ReceiveMsg;
+use cw_lib::models::{Token, TokenAmount};
+
+use crate::{
+  error::ContractError,
+  msg::{OrderRequest, ReceiveMsg},
+  state::load_token_id,
+};
+
+use super::{
+  deposit::credit_deposit,
+  submit::{build_submit_response, process_order_request},
+};
+
+pub fn receive(
+  deps: DepsMut,
+  env: Env,
+  info: MessageInfo,
+  msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+  // The sender is the CW20 contract, which must be either the base token or a
+  // registered quote token.
+  let token = Token::Cw20 {
+    address: info.sender.clone(),
+  };
+  load_token_id(deps.storage, &token)?;
+
+  let owner = deps.api.addr_validate(&msg.sender)?;
+
+  // A bare order request, as sent before deposits were supported, still
+  // submits an order.
+  let receive_msg = match from_binary::<OrderRequest>(&msg.msg) {
+    Ok(req) => ReceiveMsg::Submit(req),
+    Err(_) => from_binary::<ReceiveMsg>(&msg.msg)?,
+  };
+
+  match receive_msg {
+    ReceiveMsg::Submit(req) => {
+      let funds = TokenAmount {
+        token,
+        amount: msg.amount,
+      };
+      let (order_id, order, refund_msgs, events) =
+        process_order_request(deps.storage, &env.block, &req, &owner, Some(funds))?;
+      build_submit_response(order_id, &order, refund_msgs, events)
+    },
+    ReceiveMsg::Deposit {} => {
+      credit_deposit(deps.storage, &owner, &token, msg.amount)?;
+      Ok(Response::new().add_attributes(vec![
+        attr("action", "deposit"),
+        attr("owner", owner.to_string()),
+        attr("token", token.get_key()),
+        attr("amount", msg.amount.to_string()),
+      ]))
+    },
+  }
+}
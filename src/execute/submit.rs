@@ -1,65 +1,1150 @@
-use std::marker::PhantomData;
+use std::{collections::VecDeque, marker::PhantomData};
 
-use cosmwasm_std::{attr, Addr, DepsMut, Env, MessageInfo, Response, Storage, Timestamp, Uint128, Uint64};
-use cw_lib::models::Token;
-use cw_storage_plus::PrefixBound;
+use cosmwasm_std::{
+  attr, to_binary, Addr, BlockInfo, Coin, CosmosMsg, Decimal256, DepsMut, Env, Event, MessageInfo, Response, StdResult,
+  Storage, Uint128, Uint64,
+};
+use cw_lib::models::{Token, TokenAmount};
+use cw_storage_plus::{Bound, Map, PrefixBound};
 
 use crate::{
   error::ContractError,
-  models::{Order, OrderId, OrderKind, OrderSide, OrderStatus, TimeInForce},
-  msg::OrderRequest,
-  state::{load_token_id, ACCOUNT_ORDER_IDS, ASKS, BASE_TOKEN_ID, BIDS, ORDERS, ORDER_ID_SEQ_NO, TOKEN_BALANCES},
+  models::{
+    DustPolicy, MarketConfig, Order, OrderId, OrderKind, OrderSide, OrderStatus, PostOnly, SelfTradePrevention,
+    TimeInForce, TrailingOffset, TrailingStopPass,
+  },
+  msg::{MarketSellResult, OrderRequest},
+  state::{
+    collect_dust, decrement_locked_balance, decrement_token_balance, increment_locked_balance, increment_token_balance,
+    load_market_config, load_price_scale, load_token_by_id, load_token_id, save_client_order_id, to_price_units,
+    ACCOUNT_ORDER_IDS, ASKS, BASE_TOKEN, BASE_TOKEN_ID, BIDS, BOOK_ORDER_IDS, BUY_TRAILING_EXTREMES,
+    BUY_TRAILING_PASSES, BUY_TRAILING_STOPS, BUY_TRIGGERS, DUST_POLICY, LAST_PRICES, ORDERS, ORDER_ID_SEQ_NO,
+    SELL_TRAILING_EXTREMES, SELL_TRAILING_PASSES, SELL_TRAILING_STOPS, SELL_TRIGGERS,
+  },
+  transaction::StorageTransaction,
+  utils::{build_transfer_msg, checked_mul_price, mul_pct, mul_price},
+};
+
+use super::{
+  cancel::{close_order, get_locked_escrow},
+  group::{
+    cancel_group_siblings, is_contingent_order, process_bracket_fills, queue_bracket_fill, resize_group_siblings,
+    unlink_group_order,
+  },
 };
 
+/// Max number of stop orders triggered by any one order.
+const MAX_TRIGGERED_ORDERS: usize = 10;
+
+/// Max number of trailing stops on each side visited by any one settlement.
+/// The stops trailing furthest behind go first, and the rest catch up on later
+/// trades to the extremes traded since they were placed.
+const MAX_TRAILING_STOP_UPDATES: usize = 10;
+
 pub fn submit(
   deps: DepsMut,
   env: Env,
   info: MessageInfo,
   req: OrderRequest,
 ) -> Result<Response, ContractError> {
-  let (order_id, order) = process_order_request(deps.storage, env.block.time, &req, &info.sender)?;
-  Ok(Response::new().add_attributes(vec![
-    attr("action", "submit_order"),
-    attr("order_id", order_id.to_string()),
-    attr("order_status", format!("{:?}", order.status)),
-  ]))
+  let funds = get_native_funds(&info.funds)?;
+  let (order_id, order, refund_msgs, events) =
+    process_order_request(deps.storage, &env.block, &req, &info.sender, funds)?;
+  build_submit_response(order_id, &order, refund_msgs, events)
+}
+
+/// Source of the funds escrowed for an order.
+enum Escrow {
+  /// Funds transferred to the contract along with the order request.
+  Transfer(Token),
+  /// Funds debited from the owner's internal token balance.
+  Balance(u32),
 }
 
-fn process_order_request(
+/// Validate an order request against the trading rules of its market, place
+/// the order, and then place the exits of any bracket entries it filled.
+pub(crate) fn process_order_request(
   storage: &mut dyn Storage,
-  time: Timestamp,
+  block: &BlockInfo,
   req: &OrderRequest,
   owner: &Addr,
-) -> Result<(OrderId, Order), ContractError> {
+  funds: Option<TokenAmount>,
+) -> Result<(OrderId, Order, Vec<CosmosMsg>, Vec<Event>), ContractError> {
+  validate_market_rules(storage, req)?;
+  let (order_id, order, refund_msgs, mut events) = place_order(storage, block, req, owner, funds)?;
+  events.extend(process_bracket_fills(storage, block)?);
+  Ok((order_id, order, refund_msgs, events))
+}
+
+/// Place an order, matching it against the book or saving it as a stop order.
+/// Orders placed by the contract itself, like bracket exits sized by fills,
+/// are placed directly, without validating them against the market's rules.
+/// Along with the order, returns messages refunding unused escrow and events
+/// for any stop orders canceled when triggered.
+pub(crate) fn place_order(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  req: &OrderRequest,
+  owner: &Addr,
+  funds: Option<TokenAmount>,
+) -> Result<(OrderId, Order, Vec<CosmosMsg>, Vec<Event>), ContractError> {
   let order_id = get_next_order_id(storage)?;
-  let order = match req.clone() {
-    OrderRequest::MarketBuy { balance, tif, quote } => {
+  if let Some(client_order_id) = req.get_client_order_id() {
+    save_client_order_id(storage, owner, client_order_id, order_id)?;
+  }
+  let (order, refund_msgs, events) = place_order_with_id(storage, block, req, owner, funds, order_id, order_id)?;
+  Ok((order_id, order, refund_msgs, events))
+}
+
+/// Place an order under the given order ID, resting any limit order in the
+/// book under the given book ID. Amended orders that can't be changed in place
+/// are placed again this way, under their own ID and a new book ID.
+pub(crate) fn place_order_with_id(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  req: &OrderRequest,
+  owner: &Addr,
+  funds: Option<TokenAmount>,
+  order_id: OrderId,
+  book_id: OrderId,
+) -> Result<(Order, Vec<CosmosMsg>, Vec<Event>), ContractError> {
+  // Escrow the funds required by the order, either from funds sent with the
+  // request or from the owner's deposited balance.
+  let escrow = escrow_funds(storage, req, owner, funds)?;
+
+  let mut order = match req.clone() {
+    OrderRequest::MarketBuy {
+      balance,
+      tif,
+      quote,
+      max_price,
+      min_receive,
+      self_trade_prevention,
+      ..
+    } => {
       // Buy as many shares as possible using the given balance
-      let side = OrderSide::Buy;
+      let taker = Taker::new(owner, order_id, quote, OrderSide::Buy, tif);
       let qty = Uint128::zero();
-      match_market_order(storage, time, owner, order_id, quote, balance, qty, tif, side)?
+      let options = MarketOptions {
+        worst_price: get_optional_price_units(storage, &taker.quote_token, max_price)?,
+        min_receive,
+        self_trade_prevention,
+        quote_amount: None,
+      };
+      match_market_order(storage, block, taker, balance, qty, options)?
     },
-    OrderRequest::MarketSell { qty, tif, quote } => {
-      // Buy as many shares as possible using the given balance
-      let side = OrderSide::Sell;
+    OrderRequest::MarketBuyQty {
+      qty,
+      max_spend,
+      tif,
+      quote,
+      max_price,
+      min_receive,
+      self_trade_prevention,
+      ..
+    } => {
+      // Buy the given qty of shares, spending no more than the max spend
+      if qty.is_zero() {
+        return Err(ContractError::InvalidQuantity);
+      }
+      let taker = Taker::new(owner, order_id, quote, OrderSide::Buy, tif);
+      let options = MarketOptions {
+        worst_price: get_optional_price_units(storage, &taker.quote_token, max_price)?,
+        min_receive,
+        self_trade_prevention,
+        quote_amount: None,
+      };
+      match_market_order(storage, block, taker, max_spend, qty, options)?
+    },
+    OrderRequest::MarketSell {
+      qty,
+      tif,
+      quote,
+      min_price,
+      min_receive,
+      self_trade_prevention,
+      ..
+    } => {
+      // Sell as many shares as possible up to the given qty
+      let taker = Taker::new(owner, order_id, quote, OrderSide::Sell, tif);
       let balance = Uint128::zero();
-      match_market_order(storage, time, owner, order_id, quote, balance, qty, tif, side)?
+      let options = MarketOptions {
+        worst_price: get_optional_price_units(storage, &taker.quote_token, min_price)?,
+        min_receive,
+        self_trade_prevention,
+        quote_amount: None,
+      };
+      match_market_order(storage, block, taker, balance, qty, options)?
     },
-    OrderRequest::LimitBuy { qty, price, tif, quote } => {
+    OrderRequest::MarketSellQuote {
+      amount,
+      max_qty,
+      tif,
+      quote,
+      min_price,
+      min_receive,
+      self_trade_prevention,
+      ..
+    } => {
+      // Sell as many shares as needed to raise the given amount
+      if amount.is_zero() || max_qty.is_zero() {
+        return Err(ContractError::InvalidQuantity);
+      }
+      let taker = Taker::new(owner, order_id, quote, OrderSide::Sell, tif);
+      let balance = Uint128::zero();
+      let options = MarketOptions {
+        worst_price: get_optional_price_units(storage, &taker.quote_token, min_price)?,
+        min_receive,
+        self_trade_prevention,
+        quote_amount: Some(amount),
+      };
+      match_market_order(storage, block, taker, balance, max_qty, options)?
+    },
+    OrderRequest::LimitBuy {
+      qty,
+      price,
+      tif,
+      quote,
+      post_only,
+      display_qty,
+      min_fill_qty,
+      all_or_none,
+      self_trade_prevention,
+      ..
+    } => {
       // Only buy shares listed at the given limit price
-      let side = OrderSide::Buy;
-      match_limit_order(storage, time, owner, order_id, quote, qty, price, tif, side)?
+      let options = LimitOptions {
+        post_only,
+        display_qty,
+        min_fill_qty,
+        all_or_none: all_or_none.unwrap_or(false),
+        self_trade_prevention,
+      };
+      let price = get_price_units(storage, &quote, price)?;
+      let taker = Taker {
+        book_id,
+        ..Taker::new(owner, order_id, quote, OrderSide::Buy, tif)
+      };
+      match_limit_order(storage, block, taker, qty, price, options)?
     },
-    OrderRequest::LimitSell { qty, price, tif, quote } => {
+    OrderRequest::LimitSell {
+      qty,
+      price,
+      tif,
+      quote,
+      post_only,
+      display_qty,
+      min_fill_qty,
+      all_or_none,
+      self_trade_prevention,
+      ..
+    } => {
       // Only sell shares listed at the given limit price
-      let side = OrderSide::Sell;
-      match_limit_order(storage, time, owner, order_id, quote, qty, price, tif, side)?
+      let options = LimitOptions {
+        post_only,
+        display_qty,
+        min_fill_qty,
+        all_or_none: all_or_none.unwrap_or(false),
+        self_trade_prevention,
+      };
+      let price = get_price_units(storage, &quote, price)?;
+      let taker = Taker {
+        book_id,
+        ..Taker::new(owner, order_id, quote, OrderSide::Sell, tif)
+      };
+      match_limit_order(storage, block, taker, qty, price, options)?
+    },
+    OrderRequest::StopMarket { .. } | OrderRequest::StopLimit { .. } | OrderRequest::TrailingStop { .. } => {
+      // Wait for the last trade price to reach the trigger price
+      save_stop_order(storage, block, owner, order_id, req, true)?
+    },
+  };
+
+  if let Some(client_order_id) = req.get_client_order_id() {
+    order.client_order_id = Some(client_order_id.clone());
+    ORDERS.save(storage, order_id, &order)?;
+  }
+
+  let refund_msgs = refund_unused_escrow(storage, &order, owner, &escrow)?;
+
+  // Trigger any stop orders crossed by trades made by this order.
+  let events = process_triggers(storage, block, order.quote_token_id)?;
+
+  Ok((order, refund_msgs, events))
+}
+
+/// Build the response to a submitted order. Market sells also report the qty
+/// sold and quote received in the response data.
+pub(crate) fn build_submit_response(
+  order_id: OrderId,
+  order: &Order,
+  refund_msgs: Vec<CosmosMsg>,
+  events: Vec<Event>,
+) -> Result<Response, ContractError> {
+  let mut resp = Response::new()
+    .add_messages(refund_msgs)
+    .add_events(events)
+    .add_attributes(vec![
+      attr("action", "submit_order"),
+      attr("order_id", order_id.to_string()),
+      attr("order_status", format!("{:?}", order.status)),
+    ]);
+  if let Some(proceeds) = order.proceeds {
+    resp = resp.set_data(to_binary(&MarketSellResult {
+      order_id: order_id.into(),
+      qty_sold: order.qty_matched,
+      quote_received: proceeds,
+    })?);
+  }
+  Ok(resp)
+}
+
+/// Convert a requested price into the price units of its quote token.
+fn get_price_units(
+  storage: &dyn Storage,
+  quote: &Token,
+  price: Decimal256,
+) -> Result<Uint128, ContractError> {
+  to_price_units(storage, load_token_id(storage, quote)?, price)
+}
+
+fn get_optional_price_units(
+  storage: &dyn Storage,
+  quote: &Token,
+  maybe_price: Option<Decimal256>,
+) -> Result<Option<Uint128>, ContractError> {
+  maybe_price
+    .map(|price| get_price_units(storage, quote, price))
+    .transpose()
+}
+
+/// Get the quote amount of a qty at a requested price, rounded down.
+fn get_quote_amount(
+  storage: &dyn Storage,
+  quote: &Token,
+  qty: Uint128,
+  price: Decimal256,
+) -> Result<Uint128, ContractError> {
+  let quote_token_id = load_token_id(storage, quote)?;
+  let price = to_price_units(storage, quote_token_id, price)?;
+  checked_mul_price(qty, price, load_price_scale(storage, quote_token_id)?)
+}
+
+/// Validate an order request against the trading rules of its market. Prices
+/// must be nonzero and on a tick, and qtys on a lot and at least the min qty. The quote
+/// amount of the order must be at least the min notional, as estimated at the
+/// trigger price for stop-market sells. Market sells for a qty and trailing
+/// stop sells have no price to estimate it at.
+pub(crate) fn validate_market_rules(
+  storage: &dyn Storage,
+  req: &OrderRequest,
+) -> Result<(), ContractError> {
+  let quote_token_id = load_token_id(storage, req.get_quote())?;
+  let market = load_market_config(storage, quote_token_id)?;
+  let price_scale = load_price_scale(storage, quote_token_id)?;
+  let to_units = |price| to_price_units(storage, quote_token_id, price);
+  match req {
+    OrderRequest::MarketBuy { balance, .. } => validate_notional(&market, *balance),
+    OrderRequest::MarketBuyQty { qty, max_spend, .. } => {
+      validate_qty(&market, *qty)?;
+      validate_notional(&market, *max_spend)
+    },
+    OrderRequest::MarketSell { qty, .. } => validate_qty(&market, *qty),
+    OrderRequest::MarketSellQuote { amount, max_qty, .. } => {
+      validate_qty(&market, *max_qty)?;
+      validate_notional(&market, *amount)
+    },
+    OrderRequest::LimitBuy {
+      qty,
+      price,
+      display_qty,
+      min_fill_qty,
+      ..
+    }
+    | OrderRequest::LimitSell {
+      qty,
+      price,
+      display_qty,
+      min_fill_qty,
+      ..
+    } => {
+      let price = to_units(*price)?;
+      validate_price(&market, price)?;
+      validate_qty(&market, *qty)?;
+      for qty in [display_qty, min_fill_qty].into_iter().flatten() {
+        validate_lot(&market, *qty)?;
+      }
+      validate_notional(&market, checked_mul_price(*qty, price, price_scale)?)
+    },
+    OrderRequest::StopMarket {
+      side,
+      trigger_price,
+      amount,
+      ..
+    } => {
+      let trigger_price = to_units(*trigger_price)?;
+      validate_price(&market, trigger_price)?;
+      if *side == OrderSide::Buy {
+        validate_notional(&market, *amount)
+      } else {
+        validate_qty(&market, *amount)?;
+        validate_notional(&market, checked_mul_price(*amount, trigger_price, price_scale)?)
+      }
+    },
+    OrderRequest::StopLimit {
+      trigger_price,
+      qty,
+      price,
+      ..
+    } => {
+      let price = to_units(*price)?;
+      validate_price(&market, to_units(*trigger_price)?)?;
+      validate_price(&market, price)?;
+      validate_qty(&market, *qty)?;
+      validate_notional(&market, checked_mul_price(*qty, price, price_scale)?)
+    },
+    OrderRequest::TrailingStop {
+      side, offset, amount, ..
+    } => {
+      validate_trailing_offset(&market, offset, to_units)?;
+      if *side == OrderSide::Buy {
+        validate_notional(&market, *amount)
+      } else {
+        validate_qty(&market, *amount)
+      }
+    },
+  }
+}
+
+pub(crate) fn validate_price(
+  market: &MarketConfig,
+  price: Uint128,
+) -> Result<(), ContractError> {
+  if price.is_zero() {
+    return Err(ContractError::InvalidPrice);
+  }
+  if !(price % market.tick_size).is_zero() {
+    return Err(ContractError::PriceNotOnTick);
+  }
+  Ok(())
+}
+
+/// Trailing offsets must be nonzero, on a tick when an amount, and under 100%
+/// when a percentage.
+fn validate_trailing_offset(
+  market: &MarketConfig,
+  offset: &TrailingOffset,
+  to_units: impl Fn(Decimal256) -> Result<Uint128, ContractError>,
+) -> Result<(), ContractError> {
+  match offset {
+    TrailingOffset::Amount(amount) => {
+      if amount.is_zero() {
+        return Err(ContractError::InvalidTrailingOffset);
+      }
+      validate_price(market, to_units(*amount)?)
     },
+    TrailingOffset::Pct(pct) => {
+      if pct.is_zero() || *pct >= Uint128::from(1_000_000u128) {
+        return Err(ContractError::InvalidTrailingOffset);
+      }
+      Ok(())
+    },
+  }
+}
+
+fn validate_lot(
+  market: &MarketConfig,
+  qty: Uint128,
+) -> Result<(), ContractError> {
+  if !(qty % market.lot_size).is_zero() {
+    return Err(ContractError::QuantityNotOnLot);
+  }
+  Ok(())
+}
+
+fn validate_qty(
+  market: &MarketConfig,
+  qty: Uint128,
+) -> Result<(), ContractError> {
+  validate_lot(market, qty)?;
+  if qty < market.min_qty {
+    return Err(ContractError::QuantityBelowMin);
+  }
+  Ok(())
+}
+
+fn validate_notional(
+  market: &MarketConfig,
+  quote_amount: Uint128,
+) -> Result<(), ContractError> {
+  if quote_amount < market.min_notional {
+    return Err(ContractError::NotionalBelowMin);
+  }
+  Ok(())
+}
+
+/// Round a qty down to a multiple of a market's lot size.
+pub(crate) fn round_to_lot(
+  qty: Uint128,
+  lot_size: Uint128,
+) -> Uint128 {
+  qty - qty % lot_size
+}
+
+/// Convert native coins sent with a Submit message into a single token amount.
+/// Only one native denom may be sent with any given order.
+pub(crate) fn get_native_funds(coins: &[Coin]) -> Result<Option<TokenAmount>, ContractError> {
+  match coins {
+    [] => Ok(None),
+    [coin] => Ok(Some(TokenAmount {
+      token: Token::Native {
+        denom: coin.denom.clone(),
+      },
+      amount: coin.amount,
+    })),
+    _ => Err(ContractError::UnexpectedFunds),
+  }
+}
+
+/// Escrow the funds required by an order request. Buy orders require the quote
+/// amount they spend, while sell orders require the qty of base token they're
+/// selling. If funds are sent with the request, they must be exactly what the
+/// order requires; otherwise, the amount is drawn from the owner's balance.
+fn escrow_funds(
+  storage: &mut dyn Storage,
+  req: &OrderRequest,
+  owner: &Addr,
+  funds: Option<TokenAmount>,
+) -> Result<Escrow, ContractError> {
+  let (token_required, amount_required) = match req {
+    OrderRequest::MarketBuy { quote, balance, .. } => (quote.clone(), *balance),
+    OrderRequest::MarketBuyQty { quote, max_spend, .. } => (quote.clone(), *max_spend),
+    OrderRequest::LimitBuy { quote, qty, price, .. } => {
+      (quote.clone(), get_quote_amount(storage, quote, *qty, *price)?)
+    },
+    OrderRequest::MarketSell { qty, .. } | OrderRequest::LimitSell { qty, .. } => (BASE_TOKEN.load(storage)?, *qty),
+    OrderRequest::MarketSellQuote { max_qty, .. } => (BASE_TOKEN.load(storage)?, *max_qty),
+    OrderRequest::StopMarket {
+      quote, side, amount, ..
+    }
+    | OrderRequest::TrailingStop {
+      quote, side, amount, ..
+    } => {
+      if *side == OrderSide::Buy {
+        (quote.clone(), *amount)
+      } else {
+        (BASE_TOKEN.load(storage)?, *amount)
+      }
+    },
+    OrderRequest::StopLimit {
+      quote,
+      side,
+      qty,
+      price,
+      ..
+    } => {
+      if *side == OrderSide::Buy {
+        (quote.clone(), get_quote_amount(storage, quote, *qty, *price)?)
+      } else {
+        (BASE_TOKEN.load(storage)?, *qty)
+      }
+    },
+  };
+
+  if let Some(funds) = funds {
+    if funds.token != token_required {
+      return Err(ContractError::UnexpectedFunds);
+    }
+    if funds.amount < amount_required {
+      return Err(ContractError::InsufficientFunds);
+    }
+    if funds.amount > amount_required {
+      return Err(ContractError::ExcessFunds);
+    }
+    Ok(Escrow::Transfer(token_required))
+  } else {
+    let token_id = load_token_id(storage, &token_required)?;
+    decrement_token_balance(storage, owner, token_id, amount_required)?;
+    Ok(Escrow::Balance(token_id))
+  }
+}
+
+/// Return escrowed funds not used by an order. For buy orders, this is the
+/// remaining quote balance, less whatever is locked by an order resting in the
+/// book. For sell orders, this is the unmatched qty of an order that doesn't
+/// rest in the book. Funds drawn from the owner's balance are credited back to
+/// it; otherwise, refund messages are returned.
+fn refund_unused_escrow(
+  storage: &mut dyn Storage,
+  order: &Order,
+  owner: &Addr,
+  escrow: &Escrow,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+  // Pending stop orders keep all of their escrow locked until triggered.
+  if order.is_stop_order() {
+    return Ok(vec![]);
+  }
+  let refund_amount = if order.is_buy_side() {
+    if order.is_open() {
+      order.balance - get_locked_escrow(storage, order)?.1
+    } else {
+      order.balance
+    }
+  } else if order.is_open() {
+    Uint128::zero()
+  } else {
+    order.get_qty_unmatched()
   };
+  if refund_amount.is_zero() {
+    return Ok(vec![]);
+  }
+  match escrow {
+    Escrow::Transfer(token) => Ok(vec![build_transfer_msg(token, owner, refund_amount)?]),
+    Escrow::Balance(token_id) => {
+      increment_token_balance(storage, owner, *token_id, refund_amount)?;
+      Ok(vec![])
+    },
+  }
+}
+
+/// Save a stop order whose escrow is drawn from the owner's balance only once
+/// it's triggered, as with the stop orders in an order group.
+pub(crate) fn save_contingent_stop_order(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  owner: &Addr,
+  req: &OrderRequest,
+) -> Result<(OrderId, Order), ContractError> {
+  let order_id = get_next_order_id(storage)?;
+  if let Some(client_order_id) = req.get_client_order_id() {
+    save_client_order_id(storage, owner, client_order_id, order_id)?;
+  }
+  let order = save_stop_order(storage, block, owner, order_id, req, false)?;
   Ok((order_id, order))
 }
 
-fn get_next_order_id(storage: &mut dyn Storage) -> Result<OrderId, ContractError> {
+/// Save a stop order to the trigger book, where it waits for the last trade
+/// price to reach its trigger price. Its escrow stays locked until then.
+fn save_stop_order(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  owner: &Addr,
+  order_id: OrderId,
+  req: &OrderRequest,
+  lock_escrow: bool,
+) -> Result<Order, ContractError> {
+  let order = match req.clone() {
+    OrderRequest::StopMarket {
+      quote,
+      side,
+      trigger_price,
+      amount,
+      tif,
+      ..
+    } => {
+      if !matches!(tif, TimeInForce::Fok | TimeInForce::Ioc) {
+        return Err(ContractError::TimeInForceNotAllowed);
+      }
+      // Market buys spend a quote balance, while market sells sell a base qty.
+      let is_buy = side == OrderSide::Buy;
+      let balance = if is_buy { amount } else { Uint128::zero() };
+      let trigger_price = get_price_units(storage, &quote, trigger_price)?;
+      let quote_token_id = load_token_id(storage, &quote)?;
+      Order {
+        balance,
+        funds: balance,
+        qty_requested: if is_buy { Uint128::zero() } else { amount },
+        trigger_price: Some(trigger_price),
+        client_order_id: req.get_client_order_id().cloned(),
+        ..Order::new(owner, quote_token_id, side, OrderKind::StopMarket, &tif, block.time)
+      }
+    },
+    OrderRequest::StopLimit {
+      quote,
+      side,
+      trigger_price,
+      qty,
+      price,
+      tif,
+      ..
+    } => {
+      let trigger_price = get_price_units(storage, &quote, trigger_price)?;
+      let price = get_price_units(storage, &quote, price)?;
+      if price.is_zero() {
+        return Err(ContractError::InvalidPrice);
+      }
+      let quote_token_id = load_token_id(storage, &quote)?;
+      let funds = if side == OrderSide::Buy {
+        checked_mul_price(qty, price, load_price_scale(storage, quote_token_id)?)?
+      } else {
+        Uint128::zero()
+      };
+      Order {
+        balance: funds,
+        funds,
+        qty_requested: qty,
+        limit_price: price,
+        trigger_price: Some(trigger_price),
+        client_order_id: req.get_client_order_id().cloned(),
+        ..Order::new(owner, quote_token_id, side, OrderKind::StopLimit, &tif, block.time)
+      }
+    },
+    OrderRequest::TrailingStop {
+      quote,
+      side,
+      offset,
+      amount,
+      tif,
+      ..
+    } => {
+      if !matches!(tif, TimeInForce::Fok | TimeInForce::Ioc) {
+        return Err(ContractError::TimeInForceNotAllowed);
+      }
+      // The trigger price starts out trailing the last trade price.
+      let quote_token_id = load_token_id(storage, &quote)?;
+      let last_price = LAST_PRICES
+        .may_load(storage, quote_token_id)?
+        .ok_or(ContractError::LastPriceNotFound)?;
+      let is_buy = side == OrderSide::Buy;
+      let balance = if is_buy { amount } else { Uint128::zero() };
+      let trailing_stops = if is_buy {
+        BUY_TRAILING_STOPS
+      } else {
+        SELL_TRAILING_STOPS
+      };
+      trailing_stops.save(storage, (quote_token_id, last_price.u128(), order_id), &1)?;
+      Order {
+        balance,
+        funds: balance,
+        qty_requested: if is_buy { Uint128::zero() } else { amount },
+        trigger_price: Some(get_trailing_trigger_price(
+          storage,
+          quote_token_id,
+          is_buy,
+          last_price,
+          &offset,
+        )?),
+        trailing_offset: Some(offset),
+        trailing_price: Some(last_price),
+        client_order_id: req.get_client_order_id().cloned(),
+        ..Order::new(owner, quote_token_id, side, OrderKind::TrailingStop, &tif, block.time)
+      }
+    },
+    _ => unreachable!("not a stop order request"),
+  };
+
+  let trigger_price = order.trigger_price.unwrap_or_default();
+  if trigger_price.is_zero() {
+    return Err(ContractError::InvalidPrice);
+  }
+  validate_trigger_price(
+    storage,
+    order.quote_token_id,
+    &OrderSide::from(order.side),
+    trigger_price,
+  )?;
+
+  let triggers = if order.is_buy_side() {
+    BUY_TRIGGERS
+  } else {
+    SELL_TRIGGERS
+  };
+  triggers.save(storage, (order.quote_token_id, trigger_price.u128(), order_id), &1)?;
+
+  if lock_escrow {
+    let (token_id, locked_amount) = get_locked_escrow(storage, &order)?;
+    increment_locked_balance(storage, owner, token_id, locked_amount)?;
+  }
+
+  ORDERS.save(storage, order_id, &order)?;
+  ACCOUNT_ORDER_IDS.save(storage, (owner, order_id), &1)?;
+
+  Ok(order)
+}
+
+/// A buy stop triggers once the last trade price rises to its trigger price,
+/// and a sell stop once it falls to it, so neither can start out there.
+fn validate_trigger_price(
+  storage: &dyn Storage,
+  quote_token_id: u32,
+  side: &OrderSide,
+  trigger_price: Uint128,
+) -> Result<(), ContractError> {
+  if let Some(last_price) = LAST_PRICES.may_load(storage, quote_token_id)? {
+    let is_reached = match side {
+      OrderSide::Buy => trigger_price <= last_price,
+      OrderSide::Sell => trigger_price >= last_price,
+    };
+    if is_reached {
+      return Err(ContractError::TriggerPriceReached);
+    }
+  }
+  Ok(())
+}
+
+/// Trigger stop orders whose trigger price has been crossed by the last trade
+/// price. Since each triggered order may trade and move the last price, this
+/// repeats until no more stops are triggered, up to a max number of orders.
+/// A stop that can't be triggered, for example due to insufficient liquidity,
+/// is canceled instead, leaving no trace of the attempt but an event giving
+/// the reason. Expired stops are closed rather than triggered.
+pub(crate) fn process_triggers(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  quote_token_id: u32,
+) -> Result<Vec<Event>, ContractError> {
+  let mut events: Vec<Event> = vec![];
+  for _ in 0..MAX_TRIGGERED_ORDERS {
+    let order_id = if let Some(order_id) = find_triggered_order(storage, quote_token_id)? {
+      order_id
+    } else {
+      break;
+    };
+    let mut order = ORDERS.load(storage, order_id)?;
+    if order.is_expired(block) {
+      close_order(storage, order_id, &mut order, OrderStatus::Expired)?;
+      continue;
+    }
+    let mut tx = StorageTransaction::new(storage);
+    match trigger_stop_order(&mut tx, block, order_id) {
+      Ok(()) => tx.commit(),
+      Err(err) if is_trigger_error(&err) => {
+        drop(tx);
+        close_order(storage, order_id, &mut order, OrderStatus::Canceled)?;
+        events.push(
+          Event::new("cancel_stop_order")
+            .add_attribute("order_id", order_id.to_string())
+            .add_attribute("reason", err.to_string()),
+        );
+      },
+      Err(err) => return Err(err),
+    }
+  }
+  Ok(events)
+}
+
+/// Is the error one that a stop order can expect to fail with when triggered,
+/// depending on the book and the owner's balance at the time?
+fn is_trigger_error(err: &ContractError) -> bool {
+  matches!(
+    err,
+    ContractError::InsufficientLiquidity | ContractError::InsufficientBalance
+  )
+}
+
+/// Find the next stop order triggered by the last trade price: the lowest buy
+/// stop at or below it or else the highest sell stop at or above it.
+fn find_triggered_order(
+  storage: &dyn Storage,
+  quote_token_id: u32,
+) -> Result<Option<OrderId>, ContractError> {
+  let last_price = if let Some(last_price) = LAST_PRICES.may_load(storage, quote_token_id)? {
+    last_price.u128()
+  } else {
+    return Ok(None);
+  };
+
+  if let Some(result) = BUY_TRIGGERS
+    .prefix_range(
+      storage,
+      Some(PrefixBound::Inclusive(((quote_token_id, u128::MIN), PhantomData))),
+      Some(PrefixBound::Inclusive(((quote_token_id, last_price), PhantomData))),
+      cosmwasm_std::Order::Ascending,
+    )
+    .next()
+  {
+    let ((_, _, order_id), _) = result?;
+    return Ok(Some(order_id));
+  }
+
+  if let Some(result) = SELL_TRIGGERS
+    .prefix_range(
+      storage,
+      Some(PrefixBound::Inclusive(((quote_token_id, last_price), PhantomData))),
+      Some(PrefixBound::Inclusive(((quote_token_id, u128::MAX), PhantomData))),
+      cosmwasm_std::Order::Descending,
+    )
+    .next()
+  {
+    let ((_, _, order_id), _) = result?;
+    return Ok(Some(order_id));
+  }
+
+  Ok(None)
+}
+
+/// Convert a stop order into a live market or limit order, which keeps the
+/// stop order's ID. The locked escrow is spent by the live order, and anything
+/// unused is credited back to the owner's balance. A stop order in a group
+/// first cancels the other orders in its group and then draws its escrow from
+/// the owner's balance.
+fn trigger_stop_order(
+  storage: &mut dyn Storage,
+  block: &BlockInfo,
+  order_id: OrderId,
+) -> Result<(), ContractError> {
+  let order = ORDERS.load(storage, order_id)?;
+  let owner = order.owner.clone();
+
+  if is_contingent_order(storage, order_id, &order) {
+    cancel_group_siblings(storage, order_id)?;
+    let (token_id, escrow_amount) = get_locked_escrow(storage, &order)?;
+    decrement_token_balance(storage, &owner, token_id, escrow_amount)?;
+    increment_locked_balance(storage, &owner, token_id, escrow_amount)?;
+    unlink_group_order(storage, order_id)?;
+  }
+
+  let quote = load_token_by_id(storage, order.quote_token_id)?;
+  let taker = Taker::new(&owner, order_id, quote, OrderSide::from(order.side), order.get_tif());
+
+  let mut live_order = if order.is_stop_market_order() {
+    let (balance, qty) = (order.balance, order.qty_requested);
+    match_market_order(storage, block, taker, balance, qty, MarketOptions::default())?
+  } else {
+    let (qty, price) = (order.qty_requested, order.limit_price);
+    match_limit_order(storage, block, taker, qty, price, LimitOptions::default())?
+  };
+
+  // The live order keeps the stop order's owner-assigned ID.
+  if order.client_order_id.is_some() {
+    live_order.client_order_id = order.client_order_id.clone();
+    ORDERS.save(storage, order_id, &live_order)?;
+  }
+
+  let triggers = if order.is_buy_side() {
+    BUY_TRIGGERS
+  } else {
+    SELL_TRIGGERS
+  };
+  let trigger_price = order.trigger_price.unwrap_or_default();
+  triggers.remove(storage, (order.quote_token_id, trigger_price.u128(), order_id));
+  remove_trailing_stop(storage, order_id, &order);
+
+  let (token_id, locked_amount) = get_locked_escrow(storage, &order)?;
+  decrement_locked_balance(storage, &owner, token_id, locked_amount)?;
+  refund_unused_escrow(storage, &live_order, &owner, &Escrow::Balance(token_id))?;
+
+  Ok(())
+}
+
+/// Remove a trailing stop from the trailing stops of its market, by the best
+/// price it has reached. Other kinds of orders are ignored.
+pub(crate) fn remove_trailing_stop(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &Order,
+) {
+  if let (true, Some(trailing_price)) = (order.is_trailing_stop_order(), order.trailing_price) {
+    let trailing_stops = if order.is_buy_side() {
+      BUY_TRAILING_STOPS
+    } else {
+      SELL_TRAILING_STOPS
+    };
+    trailing_stops.remove(storage, (order.quote_token_id, trailing_price.u128(), order_id));
+  }
+}
+
+/// Get the trigger price of a trailing stop, offset from the best price reached
+/// since it was placed: the lowest price for buys and the highest for sells.
+fn get_trailing_trigger_price(
+  storage: &dyn Storage,
+  quote_token_id: u32,
+  is_buy: bool,
+  trailing_price: Uint128,
+  offset: &TrailingOffset,
+) -> Result<Uint128, ContractError> {
+  let offset_amount = match offset {
+    TrailingOffset::Amount(amount) => to_price_units(storage, quote_token_id, *amount)?,
+    TrailingOffset::Pct(pct) => mul_pct(trailing_price, *pct),
+  };
+  Ok(if is_buy {
+    trailing_price + offset_amount
+  } else {
+    trailing_price.saturating_sub(offset_amount)
+  })
+}
+
+/// Ratchet the trigger prices of a market's trailing stops to follow the range
+/// of prices just traded: down with the low for buys and up with the high for
+/// sells.
+fn update_trailing_stops(
+  storage: &mut dyn Storage,
+  quote_token_id: u32,
+  low_price: Uint128,
+  high_price: Uint128,
+) -> Result<(), ContractError> {
+  ratchet_trailing_stops(storage, quote_token_id, true, low_price)?;
+  ratchet_trailing_stops(storage, quote_token_id, false, high_price)
+}
+
+/// Ratchet a market's trailing stops on one side to an extreme price just
+/// traded. The extreme is recorded under the last order ID placed, so that it
+/// only applies to stops placed before it traded. A pass over the stops, which
+/// later settlements resume where it left off, then moves each stop it visits
+/// to the furthest extreme traded since the stop was placed. Trigger prices
+/// never move back the other way, so only buy stops trailing above the lowest
+/// extreme and sell stops trailing below the highest are visited.
+fn ratchet_trailing_stops(
+  storage: &mut dyn Storage,
+  quote_token_id: u32,
+  is_buy: bool,
+  price: Uint128,
+) -> Result<(), ContractError> {
+  let (extremes, passes, trailing_stops, triggers) = if is_buy {
+    (
+      BUY_TRAILING_EXTREMES,
+      BUY_TRAILING_PASSES,
+      BUY_TRAILING_STOPS,
+      BUY_TRIGGERS,
+    )
+  } else {
+    (
+      SELL_TRAILING_EXTREMES,
+      SELL_TRAILING_PASSES,
+      SELL_TRAILING_STOPS,
+      SELL_TRIGGERS,
+    )
+  };
+  let is_further = |a: Uint128, b: Uint128| if is_buy { a < b } else { a > b };
+  let last_order_id = ORDER_ID_SEQ_NO.load(storage)?.u64();
+
+  let mut pass = match passes.may_load(storage, quote_token_id)? {
+    Some(pass) => pass,
+    None => {
+      // Extremes only matter to stops placed before they traded.
+      let mut stops =
+        trailing_stops
+          .sub_prefix(quote_token_id)
+          .keys(storage, None, None, cosmwasm_std::Order::Ascending);
+      if stops.next().is_none() {
+        return Ok(());
+      }
+      TrailingStopPass {
+        cursor: None,
+        start_order_id: last_order_id,
+      }
+    },
+  };
+
+  // Extremes are kept from furthest to nearest, so a new extreme replaces the
+  // later ones that are no further than it.
+  let mut replaced_order_ids: Vec<OrderId> = vec![];
+  let mut is_recorded = false;
+  for result in extremes
+    .prefix(quote_token_id)
+    .range(storage, None, None, cosmwasm_std::Order::Descending)
+  {
+    let (order_id, extreme) = result?;
+    if is_further(extreme, price) {
+      is_recorded = order_id == last_order_id;
+      break;
+    }
+    replaced_order_ids.push(order_id);
+  }
+  if !is_recorded {
+    for order_id in replaced_order_ids {
+      extremes.remove(storage, (quote_token_id, order_id));
+    }
+    extremes.save(storage, (quote_token_id, last_order_id), &price)?;
+    // Stops already visited by the pass missed the new extreme, so the pass
+    // leaves it for the next one.
+    if pass.cursor.is_some() && pass.start_order_id == last_order_id {
+      pass.start_order_id = last_order_id.saturating_sub(1);
+    }
+  }
+
+  let furthest_price = extremes
+    .prefix(quote_token_id)
+    .range(storage, None, None, cosmwasm_std::Order::Ascending)
+    .next()
+    .transpose()?
+    .map_or(price, |(_, extreme)| extreme);
+
+  // Visit the stops trailing furthest behind first, from where the pass left off.
+  let cursor = pass
+    .cursor
+    .map(|(trailing_price, order_id)| (quote_token_id, trailing_price.u128(), order_id));
+  let visited = if is_buy {
+    let min = Bound::exclusive((quote_token_id, furthest_price.u128(), OrderId::MAX));
+    let max = cursor.map_or(
+      Bound::inclusive((quote_token_id, u128::MAX, OrderId::MAX)),
+      Bound::exclusive,
+    );
+    trailing_stops.keys(storage, Some(min), Some(max), cosmwasm_std::Order::Descending)
+  } else {
+    let min = cursor.map_or(
+      Bound::inclusive((quote_token_id, u128::MIN, OrderId::MIN)),
+      Bound::exclusive,
+    );
+    let max = Bound::exclusive((quote_token_id, furthest_price.u128(), OrderId::MIN));
+    trailing_stops.keys(storage, Some(min), Some(max), cosmwasm_std::Order::Ascending)
+  }
+  .take(MAX_TRAILING_STOP_UPDATES)
+  .collect::<StdResult<Vec<_>>>()?;
+
+  for (_, trailing_price, order_id) in visited.iter().copied() {
+    // The furthest extreme traded since the stop was placed is the first one
+    // recorded after it.
+    let new_trailing_price = match extremes
+      .prefix(quote_token_id)
+      .range(
+        storage,
+        Some(Bound::inclusive(order_id)),
+        None,
+        cosmwasm_std::Order::Ascending,
+      )
+      .next()
+      .transpose()?
+    {
+      Some((_, extreme)) if is_further(extreme, Uint128::new(trailing_price)) => extreme,
+      _ => continue,
+    };
+    let mut order = ORDERS.load(storage, order_id)?;
+    let offset = match order.trailing_offset.clone() {
+      Some(offset) => offset,
+      None => continue,
+    };
+
+    // Move the order to its new trailing and trigger prices.
+    let trigger_price = order.trigger_price.unwrap_or_default();
+    let new_trigger_price = get_trailing_trigger_price(storage, quote_token_id, is_buy, new_trailing_price, &offset)?;
+    triggers.remove(storage, (quote_token_id, trigger_price.u128(), order_id));
+    triggers.save(storage, (quote_token_id, new_trigger_price.u128(), order_id), &1)?;
+    trailing_stops.remove(storage, (quote_token_id, trailing_price, order_id));
+    trailing_stops.save(storage, (quote_token_id, new_trailing_price.u128(), order_id), &1)?;
+
+    order.trailing_price = Some(new_trailing_price);
+    order.trigger_price = Some(new_trigger_price);
+    ORDERS.save(storage, order_id, &order)?;
+  }
+
+  // Once the pass has visited every stop, the extremes recorded before it
+  // started have been applied to them all.
+  if visited.len() < MAX_TRAILING_STOP_UPDATES {
+    let applied_order_ids = extremes
+      .prefix(quote_token_id)
+      .keys(
+        storage,
+        None,
+        Some(Bound::inclusive(pass.start_order_id)),
+        cosmwasm_std::Order::Ascending,
+      )
+      .collect::<StdResult<Vec<_>>>()?;
+    for order_id in applied_order_ids {
+      extremes.remove(storage, (quote_token_id, order_id));
+    }
+    passes.remove(storage, quote_token_id);
+  } else {
+    pass.cursor = visited
+      .last()
+      .map(|(_, trailing_price, order_id)| (Uint128::new(*trailing_price), *order_id));
+    passes.save(storage, quote_token_id, &pass)?;
+  }
+
+  Ok(())
+}
+
+pub(crate) fn get_next_order_id(storage: &mut dyn Storage) -> Result<OrderId, ContractError> {
   Ok(
     ORDER_ID_SEQ_NO
       .update(storage, |n| -> Result<_, ContractError> { Ok(n + Uint64::one()) })?
@@ -67,157 +1152,288 @@ fn get_next_order_id(storage: &mut dyn Storage) -> Result<OrderId, ContractError
   )
 }
 
+/// A new order matched against the book as it's placed, along with its owner.
+struct Taker<'a> {
+  owner: &'a Addr,
+  order_id: OrderId,
+  /// Key of the order's place in the book, which is a new ID for an order
+  /// placed again under its own ID, so it queues behind orders at its price.
+  book_id: OrderId,
+  quote_token: Token,
+  side: OrderSide,
+  tif: TimeInForce,
+}
+
+impl<'a> Taker<'a> {
+  fn new(
+    owner: &'a Addr,
+    order_id: OrderId,
+    quote_token: Token,
+    side: OrderSide,
+    tif: TimeInForce,
+  ) -> Self {
+    Self {
+      owner,
+      order_id,
+      book_id: order_id,
+      quote_token,
+      side,
+      tif,
+    }
+  }
+}
+
+/// Optional behaviors of a market order.
+#[derive(Default)]
+struct MarketOptions {
+  /// Highest price to buy at or lowest price to sell at.
+  worst_price: Option<Uint128>,
+  /// Least qty bought or quote amount received by the order.
+  min_receive: Option<Uint128>,
+  self_trade_prevention: Option<SelfTradePrevention>,
+  /// Quote amount a market sell raises, selling no more than its qty.
+  quote_amount: Option<Uint128>,
+}
+
 fn match_market_order(
   storage: &mut dyn Storage,
-  created_at: Timestamp,
-  owner: &Addr,
-  new_order_id: OrderId,
-  quote_token: Token,
+  block: &BlockInfo,
+  taker: Taker,
   initial_balance: Uint128,
   qty_requested: Uint128,
-  tif: TimeInForce,
-  side: OrderSide,
+  options: MarketOptions,
 ) -> Result<Order, ContractError> {
+  let Taker {
+    owner,
+    order_id: new_order_id,
+    quote_token,
+    side,
+    tif,
+    ..
+  } = taker;
+  let MarketOptions {
+    worst_price,
+    min_receive,
+    self_trade_prevention,
+    quote_amount,
+  } = options;
+  if matches!(worst_price, Some(worst_price) if worst_price.is_zero()) {
+    return Err(ContractError::InvalidPrice);
+  }
+
   let quote_token_id = load_token_id(storage, &quote_token)?;
+  let price_scale = load_price_scale(storage, quote_token_id)?;
+  let lot_size = load_market_config(storage, quote_token_id)?.lot_size;
   let is_buy_req = side == OrderSide::Buy;
-  let matched_map = if is_buy_req { ASKS } else { BIDS };
-  let mut matched_orders: Vec<(OrderId, Order, u128)> = Vec::with_capacity(4);
+  // Market buys spend their whole balance unless they request a qty.
+  let is_qty_requested = !qty_requested.is_zero();
+  let self_trade = self_trade_prevention.map(|stp| (owner, stp));
+  let matches: BookMatches;
+  let mut quote_proceeds = Uint128::zero();
+  // Escrow released by decrementing the new order to prevent self-trades.
+  let mut escrow_released = Uint128::zero();
+  // Remainder of a market buy's balance too small to buy another lot.
+  let mut dust = Uint128::zero();
+  // Price of the last lot a market buy matched, if any.
+  let mut last_fill_price: Option<Uint128> = None;
   let mut new_order = Order {
-    owner: owner.clone(),
-    side: side.into(),
-    tif: tif.into(),
     balance: initial_balance,
     funds: initial_balance,
     qty_requested,
-    created_at,
-    id: None,
-    status: OrderStatus::Created.into(),
-    kind: OrderKind::Market.into(),
-    limit_price: Uint128::zero(),
-    qty_matched: Uint128::zero(),
+    ..Order::new(owner, quote_token_id, side, OrderKind::Market, &tif, block.time)
   };
 
   match OrderSide::from(new_order.side) {
     OrderSide::Buy => {
-      // Match against asks
-      for result in matched_map.prefix_range(
+      // Match against asks up to the max price, taking the whole lots affordable
+      // with the new order's remaining balance at each price, up to any qty
+      // requested.
+      let max_price = worst_price.map_or(u128::MAX, |price| price.u128());
+      matches = match_book(
         storage,
-        Some(PrefixBound::Inclusive(((quote_token_id, u128::MIN), PhantomData))),
-        Some(PrefixBound::Inclusive(((quote_token_id, u128::MAX), PhantomData))),
-        cosmwasm_std::Order::Ascending,
-      ) {
-        let ((matched_price, _, matched_order_id), _) = result?;
-        let mut matched_order = ORDERS.load(storage, matched_order_id)?;
-
-        // Get the qty affordable with the new order's remaining balance. If it
-        // can't afford anything more, stop matching.
-        let qty_needed = new_order.balance / Uint128::from(matched_price);
-        if qty_needed.is_zero() {
-          break;
-        }
-
-        let qty_available = matched_order.get_qty_unmatched();
-        let qty_delta = qty_available.min(qty_needed);
-        let balance_delta = Uint128::from(matched_price) * qty_delta;
+        block,
+        quote_token_id,
+        is_buy_req,
+        (u128::MIN, max_price),
+        self_trade,
+        |price, qty_available, qty_min, is_decrement| {
+          let mut qty_affordable = new_order
+            .balance
+            .checked_multiply_ratio(price_scale, price)
+            .unwrap_or(Uint128::MAX);
+          if is_qty_requested {
+            qty_affordable = qty_affordable.min(new_order.get_qty_unmatched());
+          }
+          qty_affordable = round_to_lot(qty_affordable, lot_size);
+          if qty_affordable.is_zero() {
+            return None;
+          }
+          let qty_delta = qty_available.min(qty_affordable);
+          if is_decrement {
+            if is_qty_requested {
+              new_order.qty_requested -= qty_delta;
+            }
+            let quote_delta = mul_price(qty_delta, price, price_scale);
+            new_order.balance -= quote_delta;
+            escrow_released += quote_delta;
+            return Some(qty_delta);
+          }
+          if qty_delta < qty_min {
+            return Some(Uint128::zero());
+          }
+          new_order.balance -= mul_price(qty_delta, price, price_scale);
+          new_order.qty_matched += qty_delta;
+          last_fill_price = Some(price);
+          Some(qty_delta)
+        },
+      )?;
 
-        new_order.balance -= balance_delta;
-        new_order.qty_matched += qty_delta;
-        matched_order.qty_matched += qty_delta;
-        if matched_order.is_qty_filled() {
-          matched_order.status = OrderStatus::Filled.into();
-        } else {
-          matched_order.status = OrderStatus::Partial.into();
+      // Only what's left of a balance that can't buy another lot even at the
+      // last price matched is dust. An order that matched nothing has none.
+      if let (false, Some(price)) = (is_qty_requested, last_fill_price) {
+        let qty_affordable = new_order
+          .balance
+          .checked_multiply_ratio(price_scale, price)
+          .unwrap_or(Uint128::MAX);
+        if round_to_lot(qty_affordable, lot_size).is_zero() {
+          dust = new_order.balance;
         }
-
-        matched_orders.push((matched_order_id, matched_order, qty_delta.into()));
       }
 
       // apply buy-side time in force
-      match TimeInForce::from(new_order.tif) {
+      match tif {
         TimeInForce::Fok => {
-          if !new_order.balance.is_zero() {
+          let is_filled = if is_qty_requested {
+            new_order.qty_matched == qty_requested
+          } else {
+            last_fill_price.is_some() && new_order.balance == dust && escrow_released.is_zero()
+          };
+          if !is_filled {
             return Err(ContractError::InsufficientLiquidity);
           }
           new_order.status = OrderStatus::Filled.into();
         },
+        TimeInForce::Ioc if matches.is_taker_canceled || is_canceled_by_decrement(&new_order, escrow_released) => {
+          let is_filled = is_qty_requested && new_order.is_qty_filled();
+          new_order.status = get_self_trade_status(&new_order, is_filled).into();
+        },
         TimeInForce::Ioc => {
           if new_order.qty_matched.is_zero() {
             return Err(ContractError::InsufficientLiquidity);
-          } else if new_order.qty_matched.is_zero() {
+          } else if is_qty_requested && new_order.qty_matched == new_order.qty_requested {
             new_order.status = OrderStatus::Filled.into();
           } else {
             new_order.status = OrderStatus::Matched.into();
           }
         },
-        TimeInForce::Gtc => {
+        TimeInForce::Gtc | TimeInForce::Gtd { .. } | TimeInForce::Gtb { .. } => {
           return Err(ContractError::TimeInForceNotAllowed);
         },
       }
     },
     OrderSide::Sell => {
-      // Match against bids
-      for result in matched_map.prefix_range(
+      // Match against bids down to the min price
+      let min_price = worst_price.map_or(u128::MIN, |price| price.u128());
+      matches = match_book(
         storage,
-        Some(PrefixBound::Inclusive(((quote_token_id, u128::MAX), PhantomData))),
-        Some(PrefixBound::Inclusive(((quote_token_id, u128::MIN), PhantomData))),
-        cosmwasm_std::Order::Descending,
-      ) {
-        let ((_, _, matched_order_id), _) = result?;
-        let mut matched_order = ORDERS.load(storage, matched_order_id)?;
-        let qty_available = matched_order.get_qty_unmatched();
-        let qty_needed = new_order.get_qty_unmatched();
-        let qty_delta = qty_available.min(qty_needed);
-
-        new_order.qty_matched += qty_delta;
-        matched_order.qty_matched += qty_delta;
-        if matched_order.is_qty_filled() {
-          matched_order.status = OrderStatus::Filled.into();
-        } else {
-          matched_order.status = OrderStatus::Partial.into();
-        }
+        block,
+        quote_token_id,
+        is_buy_req,
+        (min_price, u128::MAX),
+        self_trade,
+        |price, qty_available, qty_min, is_decrement| {
+          let mut qty_needed = new_order.get_qty_unmatched();
+          // Sell only the whole lots needed to raise the rest of any quote
+          // amount.
+          if let Some(quote_amount) = quote_amount {
+            let quote_needed = quote_amount.saturating_sub(quote_proceeds);
+            let qty_for_quote = get_qty_for_quote(quote_needed, price, price_scale);
+            qty_needed = qty_needed.min(round_to_lot(
+              qty_for_quote.saturating_add(lot_size - Uint128::one()),
+              lot_size,
+            ));
+          }
+          if qty_needed.is_zero() {
+            return None;
+          }
+          let qty_delta = qty_available.min(qty_needed);
+          if is_decrement {
+            new_order.qty_requested -= qty_delta;
+            escrow_released += qty_delta;
+            return Some(qty_delta);
+          }
+          if qty_delta < qty_min {
+            return Some(Uint128::zero());
+          }
+          // The seller is paid out of the quote funds escrowed by the bid.
+          quote_proceeds += mul_price(qty_delta, price, price_scale);
+          new_order.qty_matched += qty_delta;
+          Some(qty_delta)
+        },
+      )?;
 
-        matched_orders.push((matched_order_id, matched_order, qty_available.into()));
+      // The proceeds are exactly what the matched bids release.
+      quote_proceeds = get_quote_matched(&matches, price_scale);
 
-        if new_order.is_qty_filled() {
-          break;
-        }
-      }
+      // A sell for a quote amount is filled once it raises the amount.
+      let is_filled = match quote_amount {
+        Some(quote_amount) => quote_proceeds >= quote_amount,
+        None => new_order.is_qty_filled(),
+      };
 
-      match TimeInForce::from(new_order.tif) {
+      match tif {
         TimeInForce::Fok => {
-          if new_order.qty_matched != qty_requested {
+          if !is_filled || (quote_amount.is_none() && new_order.qty_matched != qty_requested) {
             return Err(ContractError::InsufficientLiquidity);
           }
           new_order.status = OrderStatus::Filled.into();
         },
+        TimeInForce::Ioc if matches.is_taker_canceled || is_canceled_by_decrement(&new_order, escrow_released) => {
+          new_order.status = get_self_trade_status(&new_order, is_filled).into();
+        },
         TimeInForce::Ioc => {
           if new_order.qty_matched.is_zero() {
             return Err(ContractError::InsufficientLiquidity);
-          } else if new_order.qty_matched == qty_requested {
+          } else if is_filled {
             new_order.status = OrderStatus::Filled.into();
           } else {
             new_order.status = OrderStatus::Matched.into();
           }
         },
-        TimeInForce::Gtc => {
+        TimeInForce::Gtc | TimeInForce::Gtd { .. } | TimeInForce::Gtb { .. } => {
           return Err(ContractError::TimeInForceNotAllowed);
         },
       }
+      new_order.proceeds = Some(quote_proceeds);
     },
   }
 
+  // Collect the rounding remainder of a market buy as dust, if configured to,
+  // rather than refunding it along with any unspent balance.
+  if let (false, Some(DustPolicy::Collect { recipient })) = (dust.is_zero(), DUST_POLICY.may_load(storage)?) {
+    new_order.balance -= dust;
+    collect_dust(storage, &recipient, quote_token_id, dust)?;
+  }
+
+  // Fail rather than receive less than the owner allows for slippage.
+  let amount_received = if is_buy_req {
+    new_order.qty_matched
+  } else {
+    quote_proceeds
+  };
+  if matches!(min_receive, Some(min_receive) if amount_received < min_receive) {
+    return Err(ContractError::SlippageExceeded);
+  }
+
   // Save updated matched orders and update their balances.
-  for (order_id, order, base_delta) in matched_orders.iter() {
-    let base_delta = Uint128::from(*base_delta);
-    let quote_delta = base_delta * order.limit_price;
-    let token_delta = if is_buy_req { quote_delta } else { base_delta };
+  settle_matched_orders(storage, quote_token_id, is_buy_req, &matches)?;
+  prevent_self_trades(storage, matches.canceled_orders, matches.decremented_orders)?;
+  expire_orders(storage, matches.expired_orders)?;
+
+  // Release the escrow of any qty the new order was decremented by.
+  if !escrow_released.is_zero() {
     let token_id = if is_buy_req { quote_token_id } else { BASE_TOKEN_ID };
-    if order.status == u8::from(OrderStatus::Filled) {
-      let map_key = (quote_token_id, order.limit_price.u128(), *order_id);
-      matched_map.remove(storage, map_key);
-    }
-    ORDERS.save(storage, *order_id, order)?;
-    increment_token_balance(storage, &order.owner, token_id, token_delta)?;
+    increment_token_balance(storage, owner, token_id, escrow_released)?;
   }
 
   // Save new order and update its balance.
@@ -231,100 +1447,713 @@ fn match_market_order(
     if is_buy_req {
       new_order.qty_matched
     } else {
-      new_order.get_balance_remaining()
+      quote_proceeds
     },
   )?;
 
   Ok(new_order)
 }
 
-fn increment_token_balance(
+/// Iterate over the prices and book IDs of the orders a taker may match within
+/// a price range, best price first and oldest first within a price. Bids are
+/// keyed in ascending price order like asks, so their price levels are visited
+/// in reverse while each level is still scanned from its oldest order.
+fn iter_book<'a>(
+  storage: &'a dyn Storage,
+  quote_token_id: u32,
+  is_buy_req: bool,
+  (min_price, max_price): (u128, u128),
+) -> Box<dyn Iterator<Item = StdResult<(u128, OrderId)>> + 'a> {
+  let min_bound = PrefixBound::Inclusive(((quote_token_id, min_price), PhantomData));
+  if is_buy_req {
+    return Box::new(
+      ASKS
+        .prefix_range(
+          storage,
+          Some(min_bound),
+          Some(PrefixBound::Inclusive(((quote_token_id, max_price), PhantomData))),
+          cosmwasm_std::Order::Ascending,
+        )
+        .map(|result| result.map(|((_, price, order_id), _)| (price, order_id))),
+    );
+  }
+
+  let mut next_max_price = Some(max_price);
+  let mut is_first_level = true;
+  let levels = std::iter::from_fn(move || {
+    let max_price = next_max_price.take()?;
+    let max_bound = if is_first_level {
+      is_first_level = false;
+      PrefixBound::Inclusive(((quote_token_id, max_price), PhantomData))
+    } else {
+      PrefixBound::Exclusive(((quote_token_id, max_price), PhantomData))
+    };
+    let result = BIDS
+      .prefix_range(
+        storage,
+        Some(PrefixBound::Inclusive(((quote_token_id, min_price), PhantomData))),
+        Some(max_bound),
+        cosmwasm_std::Order::Descending,
+      )
+      .next()?;
+    Some(result.map(|((_, price, _), _)| {
+      next_max_price = Some(price);
+      price
+    }))
+  });
+
+  Box::new(levels.flat_map(
+    move |result| -> Box<dyn Iterator<Item = StdResult<(u128, OrderId)>> + 'a> {
+      match result {
+        Ok(price) => Box::new(
+          BIDS
+            .prefix((quote_token_id, price))
+            .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(move |result| result.map(|order_id| (price, order_id))),
+        ),
+        Err(err) => Box::new(std::iter::once(Err(err))),
+      }
+    },
+  ))
+}
+
+/// Save orders matched from the book, removing filled ones. Each maker is paid
+/// for its matched qty out of the taker's escrow, and the maker's own escrow
+/// for that qty is released from its locked balance to the taker. Once the
+/// book is settled, fills are passed on to the makers' linked orders.
+fn settle_matched_orders(
+  storage: &mut dyn Storage,
+  quote_token_id: u32,
+  is_buy_req: bool,
+  matches: &BookMatches,
+) -> Result<(), ContractError> {
+  let matched_orders = &matches.matched_orders;
+  let matched_map = if is_buy_req { ASKS } else { BIDS };
+  let price_scale = load_price_scale(storage, quote_token_id)?;
+  for (order_id, order, base_delta) in matched_orders.iter() {
+    let base_delta = *base_delta;
+    let quote_delta = get_maker_quote_delta(order, base_delta, price_scale);
+    if order.status == u8::from(OrderStatus::Filled) {
+      let book_id = order.get_book_id(*order_id);
+      matched_map.remove(storage, (quote_token_id, order.limit_price.u128(), book_id));
+      BOOK_ORDER_IDS.remove(storage, book_id);
+    }
+    ORDERS.save(storage, *order_id, order)?;
+    if is_buy_req {
+      decrement_locked_balance(storage, &order.owner, BASE_TOKEN_ID, base_delta)?;
+      increment_token_balance(storage, &order.owner, quote_token_id, quote_delta)?;
+    } else {
+      decrement_locked_balance(storage, &order.owner, quote_token_id, quote_delta)?;
+      increment_token_balance(storage, &order.owner, BASE_TOKEN_ID, base_delta)?;
+    }
+  }
+
+  // Iceberg orders whose displayed slice filled rest a new slice at the back
+  // of the queue for their price.
+  for order_id in matches.requeued_order_ids.iter() {
+    let mut order = ORDERS.load(storage, *order_id)?;
+    if order.is_open() {
+      requeue_iceberg_order(storage, *order_id, &mut order, &matched_map)?;
+    }
+  }
+
+  // The last order matched traded at the latest price.
+  if let Some((_, order, _)) = matched_orders.last() {
+    LAST_PRICES.save(storage, quote_token_id, &order.limit_price)?;
+  }
+
+  // Trailing stops follow the full range of prices traded.
+  let prices = matched_orders.iter().map(|(_, order, _)| order.limit_price);
+  if let (Some(low_price), Some(high_price)) = (prices.clone().min(), prices.max()) {
+    update_trailing_stops(storage, quote_token_id, low_price, high_price)?;
+  }
+
+  // Resize or cancel other orders in each maker's group, and queue the exits
+  // of any bracket whose entry just filled.
+  for (order_id, order, base_delta) in matched_orders.iter() {
+    let qty_unmatched = order.get_qty_unmatched();
+    resize_group_siblings(storage, *order_id, qty_unmatched + *base_delta, qty_unmatched)?;
+    queue_bracket_fill(storage, *order_id, *base_delta)?;
+  }
+
+  Ok(())
+}
+
+/// Get the quote amount traded by a maker's fill, after the fill is applied.
+/// A bid releases the difference between the rounded quote amounts of its
+/// matched qty before and after the fill, so it never leaves any of its
+/// locked quote behind, while an ask is paid the fill's rounded quote amount.
+fn get_maker_quote_delta(
+  order: &Order,
+  base_delta: Uint128,
+  price_scale: Uint128,
+) -> Uint128 {
+  let price = order.limit_price;
+  if order.is_buy_side() {
+    let quote_matched = mul_price(order.qty_matched, price, price_scale);
+    quote_matched - mul_price(order.qty_matched - base_delta, price, price_scale)
+  } else {
+    mul_price(base_delta, price, price_scale)
+  }
+}
+
+/// Get the total quote amount traded by the makers matched by a taker.
+fn get_quote_matched(
+  matches: &BookMatches,
+  price_scale: Uint128,
+) -> Uint128 {
+  matches
+    .matched_orders
+    .iter()
+    .map(|(_, order, base_delta)| get_maker_quote_delta(order, *base_delta, price_scale))
+    .sum()
+}
+
+/// Get the least qty whose quote amount at a price covers the given amount,
+/// or the max qty if there is none.
+fn get_qty_for_quote(
+  quote_amount: Uint128,
+  price: Uint128,
+  price_scale: Uint128,
+) -> Uint128 {
+  let qty = match quote_amount.checked_multiply_ratio(price_scale, price) {
+    Ok(qty) => qty,
+    Err(_) => return Uint128::MAX,
+  };
+  if mul_price(qty, price, price_scale) < quote_amount {
+    qty + Uint128::one()
+  } else {
+    qty
+  }
+}
+
+/// Orders matched from one side of the book by a taker.
+struct BookMatches {
+  /// Each maker matched, with the qty it matched. An iceberg maker appears
+  /// again for each slice matched from its reserve.
+  matched_orders: Vec<(OrderId, Order, Uint128)>,
+  /// Expired makers skipped over, to be removed from the book.
+  expired_orders: Vec<(OrderId, Order)>,
+  /// Iceberg makers whose displayed slice filled.
+  requeued_order_ids: Vec<OrderId>,
+  /// Makers owned by the taker's owner, canceled to prevent self-trades.
+  canceled_orders: Vec<(OrderId, Order)>,
+  /// Makers owned by the taker's owner, with the qty they're decremented by.
+  decremented_orders: Vec<(OrderId, Order, Uint128)>,
+  /// Whether the taker's unmatched qty is canceled to prevent a self-trade.
+  is_taker_canceled: bool,
+}
+
+/// Match a taker against the asks or bids between the given prices, best price
+/// first. Orders at each price match in time order, after which iceberg orders
+/// whose displayed slice filled match again from their reserve, one slice at a
+/// time, at the back of the queue. For each maker, `take` is given the price,
+/// the qty available, and the least qty the maker allows to be matched, and
+/// returns the qty taken, zero to skip the maker, or None to stop matching.
+///
+/// Makers owned by the taker's owner are handled by the given self-trade
+/// prevention mode, if any, instead of matching. To decrement the taker, `take`
+/// is called as a decrement, for which it reduces the taker's qty by the qty
+/// it returns rather than trading it.
+fn match_book<F>(
+  storage: &dyn Storage,
+  block: &BlockInfo,
+  quote_token_id: u32,
+  is_buy_req: bool,
+  (min_price, max_price): (u128, u128),
+  self_trade: Option<(&Addr, SelfTradePrevention)>,
+  mut take: F,
+) -> Result<BookMatches, ContractError>
+where
+  F: FnMut(Uint128, Uint128, Uint128, bool) -> Option<Uint128>,
+{
+  let mut matches = BookMatches {
+    matched_orders: Vec::with_capacity(4),
+    expired_orders: Vec::with_capacity(2),
+    requeued_order_ids: vec![],
+    canceled_orders: vec![],
+    decremented_orders: vec![],
+    is_taker_canceled: false,
+  };
+
+  // Indices of matched iceberg orders at the current price whose displayed
+  // slice filled, in the order their next slices are queued.
+  let mut icebergs: VecDeque<usize> = VecDeque::new();
+  let mut level_price = Uint128::zero();
+
+  for result in iter_book(storage, quote_token_id, is_buy_req, (min_price, max_price)) {
+    let (matched_price, book_id) = result?;
+    let matched_price = Uint128::from(matched_price);
+
+    // Exhaust iceberg reserves at the last price before moving on to the next.
+    if matched_price != level_price {
+      if !match_iceberg_reserves(&mut matches, &mut icebergs, level_price, &mut take) {
+        return Ok(matches);
+      }
+      level_price = matched_price;
+    }
+
+    let matched_order_id = BOOK_ORDER_IDS.may_load(storage, book_id)?.unwrap_or(book_id);
+    let mut matched_order = ORDERS.load(storage, matched_order_id)?;
+
+    if matched_order.is_expired(block) {
+      matches.expired_orders.push((matched_order_id, matched_order));
+      continue;
+    }
+
+    if let Some((_, stp)) = self_trade.as_ref().filter(|(owner, _)| **owner == matched_order.owner) {
+      match stp {
+        SelfTradePrevention::CancelNewest => {
+          matches.is_taker_canceled = true;
+          return Ok(matches);
+        },
+        SelfTradePrevention::CancelOldest => {
+          matches.canceled_orders.push((matched_order_id, matched_order));
+          continue;
+        },
+        SelfTradePrevention::CancelBoth => {
+          matches.canceled_orders.push((matched_order_id, matched_order));
+          matches.is_taker_canceled = true;
+          return Ok(matches);
+        },
+        SelfTradePrevention::DecrementAndCancel => {
+          let qty_unmatched = matched_order.get_qty_unmatched();
+          let qty_delta = match take(matched_price, qty_unmatched, Uint128::zero(), true) {
+            Some(qty_delta) if qty_delta.is_zero() => continue,
+            Some(qty_delta) => qty_delta,
+            None => return Ok(matches),
+          };
+          if qty_delta < qty_unmatched {
+            matches
+              .decremented_orders
+              .push((matched_order_id, matched_order, qty_delta));
+            matches.is_taker_canceled = true;
+            return Ok(matches);
+          }
+          matches.canceled_orders.push((matched_order_id, matched_order));
+          continue;
+        },
+      }
+    }
+
+    let qty_available = matched_order.get_qty_visible();
+    let qty_delta = match take(matched_price, qty_available, matched_order.get_min_fill_qty(), false) {
+      Some(qty_delta) if qty_delta.is_zero() => continue,
+      Some(qty_delta) => qty_delta,
+      None => return Ok(matches),
+    };
+
+    fill_matched_order(&mut matched_order, qty_delta);
+    if matched_order.get_qty_visible().is_zero() && !matched_order.is_qty_filled() {
+      icebergs.push_back(matches.matched_orders.len());
+      matches.requeued_order_ids.push(matched_order_id);
+    }
+    matches
+      .matched_orders
+      .push((matched_order_id, matched_order, qty_delta));
+
+    if qty_delta < qty_available {
+      return Ok(matches);
+    }
+  }
+
+  match_iceberg_reserves(&mut matches, &mut icebergs, level_price, &mut take);
+
+  Ok(matches)
+}
+
+/// Match the reserves of iceberg orders queued at the given price, displaying
+/// one slice at a time. Returns false once the taker stops matching.
+fn match_iceberg_reserves<F>(
+  matches: &mut BookMatches,
+  icebergs: &mut VecDeque<usize>,
+  price: Uint128,
+  take: &mut F,
+) -> bool
+where
+  F: FnMut(Uint128, Uint128, Uint128, bool) -> Option<Uint128>,
+{
+  while let Some(i) = icebergs.pop_front() {
+    let (matched_order_id, matched_order, _) = &matches.matched_orders[i];
+    let (matched_order_id, mut matched_order) = (*matched_order_id, matched_order.clone());
+    matched_order.refresh_display();
+
+    let qty_available = matched_order.get_qty_visible();
+    let qty_delta = match take(price, qty_available, matched_order.get_min_fill_qty(), false) {
+      Some(qty_delta) if qty_delta.is_zero() => continue,
+      Some(qty_delta) => qty_delta,
+      None => return false,
+    };
+
+    // Each slice is settled as its own match, so the maker is paid the same
+    // rounded quote amount per slice that the taker is charged.
+    fill_matched_order(&mut matched_order, qty_delta);
+    if matched_order.get_qty_visible().is_zero() && !matched_order.is_qty_filled() {
+      icebergs.push_back(matches.matched_orders.len());
+    }
+    matches
+      .matched_orders
+      .push((matched_order_id, matched_order, qty_delta));
+
+    if qty_delta < qty_available {
+      return false;
+    }
+  }
+  true
+}
+
+/// Apply a fill to a maker order's matched qty, displayed qty, and status.
+fn fill_matched_order(
+  order: &mut Order,
+  qty_delta: Uint128,
+) {
+  order.qty_matched += qty_delta;
+  order.visible_qty = order.visible_qty.map(|qty| qty - qty_delta);
+  if order.is_qty_filled() {
+    order.status = OrderStatus::Filled.into();
+  } else {
+    order.status = OrderStatus::Partial.into();
+  }
+}
+
+/// Move an iceberg order to the back of the queue for its price under a new
+/// book ID, displaying a new slice if the last one filled.
+fn requeue_iceberg_order(
+  storage: &mut dyn Storage,
+  order_id: OrderId,
+  order: &mut Order,
+  map: &Map<(u32, u128, OrderId), u8>,
+) -> Result<(), ContractError> {
+  let (quote_token_id, price) = (order.quote_token_id, order.limit_price.u128());
+  let book_id = order.get_book_id(order_id);
+  map.remove(storage, (quote_token_id, price, book_id));
+  BOOK_ORDER_IDS.remove(storage, book_id);
+
+  let new_book_id = get_next_order_id(storage)?;
+  map.save(storage, (quote_token_id, price, new_book_id), &1)?;
+  BOOK_ORDER_IDS.save(storage, new_book_id, &order_id)?;
+
+  if order.get_qty_visible().is_zero() {
+    order.refresh_display();
+  }
+  order.book_id = Some(new_book_id);
+  ORDERS.save(storage, order_id, order)?;
+  Ok(())
+}
+
+/// Get the price at which a post-only order can rest without matching. If the
+/// requested price crosses the best price on the opposite side of the book,
+/// the order is either rejected or repriced one tick behind the best price.
+fn get_post_only_price(
+  storage: &dyn Storage,
+  block: &BlockInfo,
+  quote_token_id: u32,
+  is_buy_req: bool,
+  price: Uint128,
+  post_only: PostOnly,
+) -> Result<Uint128, ContractError> {
+  let best_price = if let Some(best_price) = load_best_price(storage, block, quote_token_id, is_buy_req)? {
+    best_price
+  } else {
+    return Ok(price);
+  };
+
+  if (is_buy_req && price < best_price) || (!is_buy_req && price > best_price) {
+    return Ok(price);
+  }
+
+  match post_only {
+    PostOnly::Reject => Err(ContractError::PostOnlyWouldMatch),
+    PostOnly::Reprice => {
+      let tick_size = load_market_config(storage, quote_token_id)?.tick_size;
+      if is_buy_req {
+        if best_price <= tick_size {
+          return Err(ContractError::PostOnlyWouldMatch);
+        }
+        Ok(best_price - tick_size)
+      } else {
+        Ok(best_price + tick_size)
+      }
+    },
+  }
+}
+
+/// Get the best unexpired price on the side of the book opposite the order.
+fn load_best_price(
+  storage: &dyn Storage,
+  block: &BlockInfo,
+  quote_token_id: u32,
+  is_buy_req: bool,
+) -> Result<Option<Uint128>, ContractError> {
+  let (map, order) = if is_buy_req {
+    (ASKS, cosmwasm_std::Order::Ascending)
+  } else {
+    (BIDS, cosmwasm_std::Order::Descending)
+  };
+  for result in map.prefix_range(
+    storage,
+    Some(PrefixBound::Inclusive(((quote_token_id, u128::MIN), PhantomData))),
+    Some(PrefixBound::Inclusive(((quote_token_id, u128::MAX), PhantomData))),
+    order,
+  ) {
+    let ((_, price, book_id), _) = result?;
+    let order_id = BOOK_ORDER_IDS.may_load(storage, book_id)?.unwrap_or(book_id);
+    if !ORDERS.load(storage, order_id)?.is_expired(block) {
+      return Ok(Some(price.into()));
+    }
+  }
+  Ok(None)
+}
+
+/// Remove expired orders encountered while matching from the book, refunding
+/// their escrow to their owners' balances.
+fn expire_orders(
+  storage: &mut dyn Storage,
+  expired_orders: Vec<(OrderId, Order)>,
+) -> Result<(), ContractError> {
+  for (order_id, mut order) in expired_orders {
+    close_order(storage, order_id, &mut order, OrderStatus::Expired)?;
+  }
+  Ok(())
+}
+
+/// Cancel or decrement makers owned by the taker's owner, as determined by the
+/// taker's self-trade prevention mode, releasing the escrow backing the qty
+/// removed from the book to their owners' balances.
+fn prevent_self_trades(
   storage: &mut dyn Storage,
-  addr: &Addr,
-  token_id: u32,
-  delta: Uint128,
+  canceled_orders: Vec<(OrderId, Order)>,
+  decremented_orders: Vec<(OrderId, Order, Uint128)>,
 ) -> Result<(), ContractError> {
-  TOKEN_BALANCES.update(storage, (addr, token_id), |maybe_balance| -> Result<_, ContractError> {
-    let balance = maybe_balance.unwrap_or_default();
-    Ok(balance + delta)
-  })?;
+  for (order_id, mut order) in canceled_orders {
+    close_order(storage, order_id, &mut order, OrderStatus::Canceled)?;
+  }
+  for (order_id, mut order, qty_delta) in decremented_orders {
+    let (token_id, locked_before) = get_locked_escrow(storage, &order)?;
+    order.qty_requested -= qty_delta;
+    order.visible_qty = order.visible_qty.map(|qty| qty.min(order.get_qty_unmatched()));
+    let escrow_delta = locked_before - get_locked_escrow(storage, &order)?.1;
+    ORDERS.save(storage, order_id, &order)?;
+    decrement_locked_balance(storage, &order.owner, token_id, escrow_delta)?;
+    increment_token_balance(storage, &order.owner, token_id, escrow_delta)?;
+  }
   Ok(())
 }
 
+/// Whether a market order was decremented to prevent self-trades without
+/// matching anything, which cancels it rather than failing for liquidity.
+fn is_canceled_by_decrement(
+  order: &Order,
+  escrow_released: Uint128,
+) -> bool {
+  !escrow_released.is_zero() && order.qty_matched.is_zero()
+}
+
+/// Get the final status of a taker whose unmatched qty is canceled to prevent
+/// a self-trade, given whether it filled what it requested before then.
+fn get_self_trade_status(
+  order: &Order,
+  is_filled: bool,
+) -> OrderStatus {
+  if order.qty_matched.is_zero() {
+    OrderStatus::Canceled
+  } else if is_filled {
+    OrderStatus::Filled
+  } else {
+    OrderStatus::Matched
+  }
+}
+
+/// Optional behaviors of a limit order.
+#[derive(Default)]
+struct LimitOptions {
+  post_only: Option<PostOnly>,
+  display_qty: Option<Uint128>,
+  min_fill_qty: Option<Uint128>,
+  all_or_none: bool,
+  self_trade_prevention: Option<SelfTradePrevention>,
+}
+
 fn match_limit_order(
   storage: &mut dyn Storage,
-  created_at: Timestamp,
-  owner: &Addr,
-  new_order_id: OrderId,
-  quote_token: Token,
+  block: &BlockInfo,
+  taker: Taker,
   qty_requested: Uint128,
   price: Uint128,
-  tif: TimeInForce,
-  side: OrderSide,
+  options: LimitOptions,
 ) -> Result<Order, ContractError> {
+  let Taker {
+    owner,
+    order_id: new_order_id,
+    book_id,
+    quote_token,
+    side,
+    tif,
+  } = taker;
+  let LimitOptions {
+    post_only,
+    display_qty,
+    min_fill_qty,
+    all_or_none,
+    self_trade_prevention,
+  } = options;
+  if price.is_zero() {
+    return Err(ContractError::InvalidPrice);
+  }
+
   let quote_token_id = load_token_id(storage, &quote_token)?;
+  let price_scale = load_price_scale(storage, quote_token_id)?;
   let is_buy_req = side == OrderSide::Buy;
-  let matched_map = if is_buy_req { ASKS } else { BIDS };
-  let mut matched_orders: Vec<(OrderId, Order, Uint128)> = Vec::with_capacity(4);
+  let self_trade = self_trade_prevention.clone().map(|stp| (owner, stp));
+  // Escrow released by decrementing the new order to prevent self-trades.
+  let mut escrow_released = Uint128::zero();
+  let expiration = tif.get_expiration();
+
+  if matches!(&expiration, Some(expiration) if expiration.is_expired(block)) {
+    return Err(ContractError::InvalidExpiration);
+  }
+
+  // Buy orders spend down the quote funds escrowed at the requested price.
+  let funds = if is_buy_req {
+    checked_mul_price(qty_requested, price, price_scale)?
+  } else {
+    Uint128::zero()
+  };
+
+  // An iceberg order must rest in the book to display its slices, and it can't
+  // be all-or-none, since only a slice of it can be matched at a time.
+  if let Some(display_qty) = display_qty {
+    if display_qty.is_zero() || all_or_none {
+      return Err(ContractError::InvalidQuantity);
+    }
+    if !tif.is_resting() {
+      return Err(ContractError::TimeInForceNotAllowed);
+    }
+  }
+
+  if matches!(min_fill_qty, Some(min_fill_qty) if min_fill_qty.is_zero() || min_fill_qty > qty_requested) {
+    return Err(ContractError::InvalidQuantity);
+  }
+
+  // A post-only order must rest in the book without matching on arrival.
+  let price = if let Some(post_only) = post_only.clone() {
+    if !tif.is_resting() {
+      return Err(ContractError::TimeInForceNotAllowed);
+    }
+    get_post_only_price(storage, block, quote_token_id, is_buy_req, price, post_only)?
+  } else {
+    price
+  };
+
   let mut new_order = Order {
+    balance: funds,
+    funds,
     qty_requested,
-    created_at,
-    owner: owner.clone(),
     limit_price: price,
-    id: None,
-    tif: tif.into(),
-    side: side.into(),
-    qty_matched: Uint128::zero(),
-    funds: Uint128::zero(),
-    status: OrderStatus::Created.into(),
-    kind: OrderKind::Limit.into(),
-    balance: Uint128::zero(),
+    display_qty,
+    min_fill_qty,
+    all_or_none: Some(all_or_none),
+    post_only,
+    self_trade_prevention,
+    ..Order::new(owner, quote_token_id, side, OrderKind::Limit, &tif, block.time)
   };
 
-  for result in
-    matched_map
-      .prefix((quote_token_id, price.into()))
-      .keys(storage, None, None, cosmwasm_std::Order::Ascending)
-  {
-    let matched_order_id = result?;
-    let mut matched_order = ORDERS.load(storage, matched_order_id)?;
-    let qty_needed = new_order.get_qty_unmatched();
-    let qty_available = matched_order.get_qty_unmatched();
-    let qty_delta = qty_available.min(qty_needed);
-
-    new_order.qty_matched += qty_delta;
-    matched_order.qty_matched += qty_delta;
-    if matched_order.is_qty_filled() {
-      matched_order.status = OrderStatus::Filled.into();
-    } else {
-      matched_order.status = OrderStatus::Partial.into();
-    }
+  // Sweep every price level that crosses the limit price, best price first:
+  // asks up to the limit for buys and bids down to the limit for sells.
+  let price_range = if is_buy_req {
+    (u128::MIN, price.u128())
+  } else {
+    (price.u128(), u128::MAX)
+  };
 
-    matched_orders.push((matched_order_id, matched_order, qty_available.into()));
+  let mut matches = match_book(
+    storage,
+    block,
+    quote_token_id,
+    is_buy_req,
+    price_range,
+    self_trade,
+    |matched_price, qty_available, qty_min, is_decrement| {
+      let qty_needed = new_order.get_qty_unmatched();
+      if qty_needed.is_zero() {
+        return None;
+      }
 
-    if new_order.is_qty_filled() {
-      break;
+      // Decrementing the new order releases the escrow of the qty removed.
+      if is_decrement {
+        let qty_delta = qty_available.min(qty_needed);
+        let qty_before = new_order.qty_requested;
+        new_order.qty_requested -= qty_delta;
+        if is_buy_req {
+          let quote_before = mul_price(qty_before, price, price_scale);
+          let quote_delta = quote_before - mul_price(new_order.qty_requested, price, price_scale);
+          new_order.balance -= quote_delta;
+          escrow_released += quote_delta;
+        } else {
+          escrow_released += qty_delta;
+        }
+        return Some(qty_delta);
+      }
+
+      // Skip makers that can't fill the new order's min fill qty, or whose own
+      // min fill qty is more than the new order needs.
+      let qty_delta = qty_available.min(qty_needed);
+      let new_order_qty_min = min_fill_qty.unwrap_or_default().min(qty_needed);
+      if qty_delta < qty_min || qty_delta < new_order_qty_min {
+        return Some(Uint128::zero());
+      }
+
+      // Trades execute at the maker's price. For buys, any improvement on the
+      // limit price stays in the order's balance and is refunded.
+      if is_buy_req {
+        new_order.balance -= mul_price(qty_delta, matched_price, price_scale);
+      }
+
+      new_order.qty_matched += qty_delta;
+      Some(qty_delta)
+    },
+  )?;
+
+  // An all-or-none order doesn't match at all unless it fills in full.
+  if all_or_none && new_order.qty_matched != qty_requested {
+    matches.matched_orders.clear();
+    matches.requeued_order_ids.clear();
+    new_order.qty_matched = Uint128::zero();
+    new_order.balance = funds;
+    if is_buy_req {
+      new_order.balance -= escrow_released;
     }
   }
 
-  match TimeInForce::from(new_order.tif) {
+  // The proceeds of a sell are exactly what the matched bids release.
+  let quote_proceeds = if is_buy_req {
+    Uint128::zero()
+  } else {
+    get_quote_matched(&matches, price_scale)
+  };
+
+  match tif {
     TimeInForce::Fok => {
       if new_order.qty_matched != qty_requested {
         return Err(ContractError::InsufficientLiquidity);
       }
       new_order.status = OrderStatus::Filled.into();
     },
+    // An order decremented away entirely is canceled along with the maker.
+    _ if matches.is_taker_canceled || new_order.qty_requested.is_zero() => {
+      new_order.status = get_self_trade_status(&new_order, new_order.is_qty_filled()).into();
+    },
     TimeInForce::Ioc => {
       if new_order.qty_matched.is_zero() {
         return Err(ContractError::InsufficientLiquidity);
-      } else if new_order.qty_matched == qty_requested {
+      } else if new_order.qty_matched == new_order.qty_requested {
         new_order.status = OrderStatus::Filled.into();
       } else {
         new_order.status = OrderStatus::Matched.into();
       }
     },
-    TimeInForce::Gtc => {
-      if new_order.qty_matched == qty_requested {
+    TimeInForce::Gtc | TimeInForce::Gtd { .. } | TimeInForce::Gtb { .. } => {
+      if new_order.qty_matched == new_order.qty_requested {
         new_order.status = OrderStatus::Filled.into();
       } else if !new_order.qty_matched.is_zero() {
         new_order.status = OrderStatus::Partial.into();
@@ -332,25 +2161,29 @@ fn match_limit_order(
     },
   }
 
-  for (order_id, order, qty_delta) in matched_orders.iter() {
-    if order.status == u8::from(OrderStatus::Filled) {
-      let qty_delta = *qty_delta;
-      ORDERS.save(storage, *order_id, order)?;
-      matched_map.remove(storage, (quote_token_id, price.into(), *order_id));
-      increment_token_balance(
-        storage,
-        &order.owner,
-        if is_buy_req { quote_token_id } else { BASE_TOKEN_ID },
-        if is_buy_req { qty_delta * price } else { qty_delta },
-      )?;
-    }
+  settle_matched_orders(storage, quote_token_id, is_buy_req, &matches)?;
+  prevent_self_trades(storage, matches.canceled_orders, matches.decremented_orders)?;
+  expire_orders(storage, matches.expired_orders)?;
+
+  // Release the escrow of any qty the new order was decremented by.
+  if !escrow_released.is_zero() {
+    let token_id = if is_buy_req { quote_token_id } else { BASE_TOKEN_ID };
+    increment_token_balance(storage, owner, token_id, escrow_released)?;
   }
 
   let status: OrderStatus = new_order.status.into();
 
+  // Rest the unmatched qty in the book, locking the escrow that backs it.
   if status == OrderStatus::Partial || status == OrderStatus::Created {
+    new_order.refresh_display();
     let map = if new_order.is_buy_side() { BIDS } else { ASKS };
-    map.save(storage, (quote_token_id, price.into(), new_order_id), &1)?;
+    if book_id != new_order_id {
+      BOOK_ORDER_IDS.save(storage, book_id, &new_order_id)?;
+      new_order.book_id = Some(book_id);
+    }
+    map.save(storage, (quote_token_id, price.into(), book_id), &1)?;
+    let (token_id, escrow_locked) = get_locked_escrow(storage, &new_order)?;
+    increment_locked_balance(storage, owner, token_id, escrow_locked)?;
   }
 
   ORDERS.save(storage, new_order_id, &new_order)?;
@@ -363,7 +2196,7 @@ fn match_limit_order(
     if is_buy_req {
       new_order.qty_matched
     } else {
-      new_order.qty_matched * price
+      quote_proceeds
     },
   )?;
 
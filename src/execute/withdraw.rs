@@ -0,0 +1,86 @@
+use cosmwasm_std::{attr, Addr, DepsMut, Env, MessageInfo, Response, Uint128};
+use cw_lib::models::Token;
+
+use crate::{
+  error::ContractError,
+  state::{decrement_token_balance, load_token_by_id, load_token_id, TOKEN_BALANCES},
+  utils::build_transfer_msg,
+};
+
+pub fn withdraw(
+  deps: DepsMut,
+  _env: Env,
+  info: MessageInfo,
+  token: Token,
+  maybe_amount: Option<Uint128>,
+  maybe_recipient: Option<Addr>,
+) -> Result<Response, ContractError> {
+  let recipient = validate_recipient(&deps, &info, maybe_recipient)?;
+  let token_id = load_token_id(deps.storage, &token)?;
+
+  // Withdraw the full balance if no amount is given
+  let amount = if let Some(amount) = maybe_amount {
+    amount
+  } else {
+    TOKEN_BALANCES
+      .may_load(deps.storage, (&info.sender, token_id))?
+      .unwrap_or_default()
+  };
+
+  if amount.is_zero() {
+    return Err(ContractError::InsufficientBalance);
+  }
+
+  decrement_token_balance(deps.storage, &info.sender, token_id, amount)?;
+
+  Ok(
+    Response::new()
+      .add_message(build_transfer_msg(&token, &recipient, amount)?)
+      .add_attributes(vec![
+        attr("action", "withdraw"),
+        attr("token", token.get_key()),
+        attr("amount", amount.to_string()),
+        attr("recipient", recipient.to_string()),
+      ]),
+  )
+}
+
+pub fn withdraw_all(
+  deps: DepsMut,
+  _env: Env,
+  info: MessageInfo,
+  maybe_recipient: Option<Addr>,
+) -> Result<Response, ContractError> {
+  let recipient = validate_recipient(&deps, &info, maybe_recipient)?;
+  let mut resp = Response::new().add_attributes(vec![
+    attr("action", "withdraw_all"),
+    attr("recipient", recipient.to_string()),
+  ]);
+
+  let balances: Vec<(u32, Uint128)> = TOKEN_BALANCES
+    .prefix(&info.sender)
+    .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+    .collect::<Result<Vec<_>, _>>()?;
+
+  for (token_id, amount) in balances {
+    TOKEN_BALANCES.remove(deps.storage, (&info.sender, token_id));
+    if !amount.is_zero() {
+      let token = load_token_by_id(deps.storage, token_id)?;
+      resp = resp.add_message(build_transfer_msg(&token, &recipient, amount)?);
+    }
+  }
+
+  Ok(resp)
+}
+
+fn validate_recipient(
+  deps: &DepsMut,
+  info: &MessageInfo,
+  maybe_recipient: Option<Addr>,
+) -> Result<Addr, ContractError> {
+  if let Some(recipient) = maybe_recipient {
+    Ok(deps.api.addr_validate(recipient.as_str())?)
+  } else {
+    Ok(info.sender.clone())
+  }
+}
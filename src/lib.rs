@@ -10,4 +10,5 @@ pub mod query;
 #[cfg(not(feature = "library"))]
 pub mod reply;
 pub mod state;
+pub mod transaction;
 pub mod utils;
@@ -1,5 +1,6 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Timestamp, Uint128, Uint64};
+use cosmwasm_std::{Addr, BlockInfo, Decimal256, Timestamp, Uint128, Uint64};
+use cw_utils::Expiration;
 
 pub type OrderId = u64;
 
@@ -9,21 +10,29 @@ pub const SELL: u8 = 2;
 pub const FOK: u8 = 1;
 pub const IOC: u8 = 2;
 pub const GTC: u8 = 3;
+pub const GTD: u8 = 4;
+pub const GTB: u8 = 5;
 
 pub const MARKET: u8 = 1;
 pub const LIMIT: u8 = 2;
+pub const STOP_MARKET: u8 = 3;
+pub const STOP_LIMIT: u8 = 4;
+pub const TRAILING_STOP: u8 = 5;
 
 pub const CREATED: u8 = 1;
 pub const MATCHED: u8 = 2;
 pub const PARTIAL: u8 = 3;
 pub const FILLED: u8 = 4;
 pub const CANCELED: u8 = 5;
+pub const EXPIRED: u8 = 6;
 
 #[cw_serde]
 pub enum TimeInForce {
   Fok,
   Ioc,
   Gtc,
+  Gtd { expires_at: Timestamp },
+  Gtb { height: u64 },
 }
 
 impl From<TimeInForce> for u8 {
@@ -32,21 +41,94 @@ impl From<TimeInForce> for u8 {
       TimeInForce::Fok => FOK,
       TimeInForce::Ioc => IOC,
       TimeInForce::Gtc => GTC,
+      TimeInForce::Gtd { .. } => GTD,
+      TimeInForce::Gtb { .. } => GTB,
     }
   }
 }
 
-impl From<u8> for TimeInForce {
-  fn from(value: u8) -> Self {
-    match value {
-      FOK => TimeInForce::Fok,
-      IOC => TimeInForce::Ioc,
-      GTC => TimeInForce::Gtc,
-      _ => panic!("Invalid u8 value for TimeInForce"),
+impl TimeInForce {
+  /// Can an order with this time in force rest in the book?
+  pub fn is_resting(&self) -> bool {
+    matches!(
+      self,
+      TimeInForce::Gtc | TimeInForce::Gtd { .. } | TimeInForce::Gtb { .. }
+    )
+  }
+
+  pub fn get_expiration(&self) -> Option<Expiration> {
+    match self {
+      TimeInForce::Gtd { expires_at } => Some(Expiration::AtTime(*expires_at)),
+      TimeInForce::Gtb { height } => Some(Expiration::AtHeight(*height)),
+      _ => None,
+    }
+  }
+}
+
+/// Distance of a trailing stop's trigger price from the best price reached
+/// since it was placed, either as an absolute amount of quote per base, like
+/// other prices in requests, or as a percentage, where 1000000 is 100%.
+#[cw_serde]
+pub enum TrailingOffset {
+  Amount(Decimal256),
+  Pct(Uint128),
+}
+
+/// Handling of a post-only limit order that would match on arrival.
+#[cw_serde]
+pub enum PostOnly {
+  Reject,
+  Reprice,
+}
+
+/// Trading rules of the market for a quote token. Prices must be multiples of
+/// the tick size, in price units, and qtys multiples of the lot size. Orders
+/// must be for at least the min qty and the min notional quote amount.
+#[cw_serde]
+pub struct MarketConfig {
+  /// Number of decimal places allowed in prices.
+  pub price_precision: u8,
+  pub tick_size: Uint128,
+  pub lot_size: Uint128,
+  pub min_qty: Uint128,
+  pub min_notional: Uint128,
+}
+
+impl Default for MarketConfig {
+  fn default() -> Self {
+    Self {
+      price_precision: 0,
+      tick_size: Uint128::one(),
+      lot_size: Uint128::one(),
+      min_qty: Uint128::zero(),
+      min_notional: Uint128::zero(),
     }
   }
 }
 
+/// Handling of the remainder of a market buy's balance that's too small to buy
+/// another lot at the last price it matched, after rounding the qty bought down.
+/// A market buy that matches nothing leaves no dust.
+#[cw_serde]
+pub enum DustPolicy {
+  /// Refund the remainder to the taker along with any unspent balance.
+  Refund,
+  /// Credit the remainder to the recipient's balance, from which it can be
+  /// withdrawn, accumulating a total of the dust collected per quote token.
+  Collect { recipient: Addr },
+}
+
+/// Handling of a new order that would match a resting order from its owner.
+/// With decrement-and-cancel, the smaller of the two orders is canceled, and
+/// the larger is decremented by the smaller's qty without trading.
+#[cw_serde]
+pub enum SelfTradePrevention {
+  CancelNewest,
+  CancelOldest,
+  CancelBoth,
+  DecrementAndCancel,
+}
+
 #[cw_serde]
 pub enum OrderSide {
   Buy,
@@ -76,6 +158,9 @@ impl From<u8> for OrderSide {
 pub enum OrderKind {
   Market,
   Limit,
+  StopMarket,
+  StopLimit,
+  TrailingStop,
 }
 
 impl From<OrderKind> for u8 {
@@ -83,6 +168,9 @@ impl From<OrderKind> for u8 {
     match value {
       OrderKind::Market => MARKET,
       OrderKind::Limit => LIMIT,
+      OrderKind::StopMarket => STOP_MARKET,
+      OrderKind::StopLimit => STOP_LIMIT,
+      OrderKind::TrailingStop => TRAILING_STOP,
     }
   }
 }
@@ -92,6 +180,9 @@ impl From<u8> for OrderKind {
     match value {
       MARKET => OrderKind::Market,
       LIMIT => OrderKind::Limit,
+      STOP_MARKET => OrderKind::StopMarket,
+      STOP_LIMIT => OrderKind::StopLimit,
+      TRAILING_STOP => OrderKind::TrailingStop,
       _ => panic!("Invalid u8 value for OrderKind"),
     }
   }
@@ -104,6 +195,7 @@ pub enum OrderStatus {
   Partial,
   Filled,
   Canceled,
+  Expired,
 }
 
 impl From<OrderStatus> for u8 {
@@ -114,6 +206,7 @@ impl From<OrderStatus> for u8 {
       OrderStatus::Partial => PARTIAL,
       OrderStatus::Filled => FILLED,
       OrderStatus::Canceled => CANCELED,
+      OrderStatus::Expired => EXPIRED,
     }
   }
 }
@@ -126,6 +219,7 @@ impl From<u8> for OrderStatus {
       MATCHED => OrderStatus::Matched,
       FILLED => OrderStatus::Filled,
       CANCELED => OrderStatus::Canceled,
+      EXPIRED => OrderStatus::Expired,
       _ => panic!("Invalid u8 value for OrderStatus"),
     }
   }
@@ -135,19 +229,100 @@ impl From<u8> for OrderStatus {
 pub struct Order {
   pub id: Option<Uint64>,
   pub owner: Addr,
+  pub quote_token_id: u32,
   pub created_at: Timestamp,
   pub side: u8,
   pub kind: u8,
   pub tif: u8,
+  pub expiration: Option<Expiration>,
   pub status: u8,
   pub balance: Uint128,
   pub funds: Uint128,
   pub qty_matched: Uint128,
   pub qty_requested: Uint128,
+  /// Prices are in the market's price units, which are fractions of the quote
+  /// token determined by its price precision.
   pub limit_price: Uint128,
+  pub trigger_price: Option<Uint128>,
+  pub trailing_offset: Option<TrailingOffset>,
+  pub trailing_price: Option<Uint128>,
+  pub display_qty: Option<Uint128>,
+  pub visible_qty: Option<Uint128>,
+  pub book_id: Option<OrderId>,
+  pub min_fill_qty: Option<Uint128>,
+  pub all_or_none: Option<bool>,
+  /// Options of a limit order, kept so that amending the order keeps them.
+  pub post_only: Option<PostOnly>,
+  pub self_trade_prevention: Option<SelfTradePrevention>,
+  pub client_order_id: Option<String>,
+  /// Quote amount received by a market sell order.
+  pub proceeds: Option<Uint128>,
+}
+
+/// Exit orders of a bracket, placed as its entry order fills: a take-profit
+/// limit order and a stop-loss stop-market order on the opposite side, which
+/// form a one-cancels-other group. The group ID is the entry order's ID.
+#[cw_serde]
+pub struct Bracket {
+  /// Exit prices, in the market's price units.
+  pub take_profit_price: Uint128,
+  pub stop_loss_price: Uint128,
+  pub take_profit_order_id: Option<OrderId>,
+  pub stop_loss_order_id: Option<OrderId>,
+}
+
+/// Progress of a pass over a market's trailing stops on one side, which
+/// ratchets them to the extreme prices traded since they were placed, a
+/// limited number of stops per settlement.
+#[cw_serde]
+pub struct TrailingStopPass {
+  /// Trailing price and order ID of the last stop visited.
+  pub cursor: Option<(Uint128, OrderId)>,
+  /// Extremes recorded under this order ID or earlier are applied to every
+  /// stop by the end of the pass.
+  pub start_order_id: OrderId,
 }
 
 impl Order {
+  /// Create a new order with nothing requested, matched, or escrowed yet.
+  pub fn new(
+    owner: &Addr,
+    quote_token_id: u32,
+    side: OrderSide,
+    kind: OrderKind,
+    tif: &TimeInForce,
+    created_at: Timestamp,
+  ) -> Self {
+    Self {
+      id: None,
+      owner: owner.clone(),
+      quote_token_id,
+      created_at,
+      side: side.into(),
+      kind: kind.into(),
+      tif: tif.clone().into(),
+      expiration: tif.get_expiration(),
+      status: OrderStatus::Created.into(),
+      balance: Uint128::zero(),
+      funds: Uint128::zero(),
+      qty_matched: Uint128::zero(),
+      qty_requested: Uint128::zero(),
+      limit_price: Uint128::zero(),
+      trigger_price: None,
+      trailing_offset: None,
+      trailing_price: None,
+      display_qty: None,
+      visible_qty: None,
+      book_id: None,
+      min_fill_qty: None,
+      all_or_none: None,
+      post_only: None,
+      self_trade_prevention: None,
+      client_order_id: None,
+      proceeds: None,
+    }
+  }
+
   pub fn get_qty_unmatched(&self) -> Uint128 {
     if self.qty_requested.is_zero() {
       return Uint128::zero();
@@ -180,6 +355,89 @@ impl Order {
   pub fn is_market_order(&self) -> bool {
     self.kind == u8::from(OrderKind::Market)
   }
+
+  /// Does this stop order become a market order when triggered? Trailing
+  /// stops always do.
+  pub fn is_stop_market_order(&self) -> bool {
+    self.kind == u8::from(OrderKind::StopMarket) || self.kind == u8::from(OrderKind::TrailingStop)
+  }
+
+  pub fn is_iceberg_order(&self) -> bool {
+    self.display_qty.is_some()
+  }
+
+  /// Get the unmatched qty shown in the book. For iceberg orders, this is
+  /// what's left of the displayed slice; the rest is hidden in reserve.
+  pub fn get_qty_visible(&self) -> Uint128 {
+    self.visible_qty.unwrap_or_else(|| self.get_qty_unmatched())
+  }
+
+  pub fn is_all_or_none(&self) -> bool {
+    self.all_or_none.unwrap_or(false)
+  }
+
+  /// Get the least qty that may be matched against the order at once: all of
+  /// its unmatched qty if it's all-or-none, or else its min fill qty, up to
+  /// what's available.
+  pub fn get_min_fill_qty(&self) -> Uint128 {
+    if self.is_all_or_none() {
+      self.get_qty_unmatched()
+    } else {
+      self.min_fill_qty.unwrap_or_default().min(self.get_qty_visible())
+    }
+  }
+
+  /// Display a new slice of an iceberg order from its hidden reserve.
+  pub fn refresh_display(&mut self) {
+    if let Some(display_qty) = self.display_qty {
+      self.visible_qty = Some(display_qty.min(self.get_qty_unmatched()));
+    }
+  }
+
+  /// Get the ID under which the order rests in the book. This is the order's
+  /// own ID unless it's an iceberg order that has displayed a new slice.
+  pub fn get_book_id(
+    &self,
+    order_id: OrderId,
+  ) -> OrderId {
+    self.book_id.unwrap_or(order_id)
+  }
+
+  pub fn is_trailing_stop_order(&self) -> bool {
+    self.kind == u8::from(OrderKind::TrailingStop)
+  }
+
+  pub fn is_stop_order(&self) -> bool {
+    self.kind == u8::from(OrderKind::StopMarket)
+      || self.kind == u8::from(OrderKind::StopLimit)
+      || self.kind == u8::from(OrderKind::TrailingStop)
+  }
+
+  pub fn is_open(&self) -> bool {
+    self.status == u8::from(OrderStatus::Created) || self.status == u8::from(OrderStatus::Partial)
+  }
+
+  /// Expired orders aren't removed from the book when they expire, but only
+  /// once a taker comes across them while matching, so anything reading the
+  /// book must skip them.
+  pub fn is_expired(
+    &self,
+    block: &BlockInfo,
+  ) -> bool {
+    matches!(&self.expiration, Some(expiration) if expiration.is_expired(block))
+  }
+
+  pub fn get_tif(&self) -> TimeInForce {
+    match (self.tif, &self.expiration) {
+      (FOK, _) => TimeInForce::Fok,
+      (IOC, _) => TimeInForce::Ioc,
+      (GTD, Some(Expiration::AtTime(expires_at))) => TimeInForce::Gtd {
+        expires_at: *expires_at,
+      },
+      (GTB, Some(Expiration::AtHeight(height))) => TimeInForce::Gtb { height: *height },
+      _ => TimeInForce::Gtc,
+    }
+  }
 }
 
 impl OrderSide {
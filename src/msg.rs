@@ -1,6 +1,9 @@
-use crate::models::{Order, TimeInForce};
+use crate::models::{
+  DustPolicy, Order, OrderSide, OrderStatus, PostOnly, SelfTradePrevention, TimeInForce, TrailingOffset,
+};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128, Uint64};
+use cosmwasm_std::{Addr, Decimal256, Uint128, Uint64};
+use cw20::Cw20ReceiveMsg;
 use cw20_base::msg::InstantiateMarketingInfo;
 use cw_lib::models::{Token, TokenAmount};
 
@@ -14,15 +17,80 @@ pub struct BaseTokenInitArgs {
   pub cap: Uint128,
 }
 
+/// A quote token to trade the base token against, with the number of decimal
+/// places allowed in its prices and the trading rules of its market.
+#[cw_serde]
+pub struct QuoteTokenInitArgs {
+  pub token: Token,
+  pub price_precision: u8,
+  /// Least price increment, one price unit by default.
+  pub tick_size: Option<Decimal256>,
+  /// Qty increment of base token, one by default.
+  pub lot_size: Option<Uint128>,
+  /// Least qty of base token per order.
+  pub min_qty: Option<Uint128>,
+  /// Least quote amount per order.
+  pub min_notional: Option<Uint128>,
+}
+
 #[cw_serde]
 pub struct InstantiateMsg {
   pub base_token: BaseTokenInitArgs,
-  pub quote_tokens: Vec<Token>,
+  pub quote_tokens: Vec<QuoteTokenInitArgs>,
+  /// Handling of market buy rounding remainders, refunded by default.
+  pub dust_policy: Option<DustPolicy>,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
   Submit(OrderRequest),
+  SubmitBatch {
+    orders: Vec<OrderRequest>,
+    atomic: bool,
+  },
+  SubmitOco {
+    a: OrderRequest,
+    b: OrderRequest,
+  },
+  SubmitBracket {
+    entry: OrderRequest,
+    take_profit_price: Decimal256,
+    stop_loss_price: Decimal256,
+  },
+  Receive(Cw20ReceiveMsg),
+  Deposit {},
+  Cancel {
+    order_id: Option<Uint64>,
+    client_order_id: Option<String>,
+  },
+  Amend {
+    order_id: Option<Uint64>,
+    client_order_id: Option<String>,
+    new_qty: Option<Uint128>,
+    new_price: Option<Decimal256>,
+  },
+  CancelAll {
+    quote: Option<Token>,
+    side: Option<OrderSide>,
+    price_range: Option<(Decimal256, Decimal256)>,
+    limit: Option<u8>,
+    cursor: Option<Uint64>,
+  },
+  Withdraw {
+    token: Token,
+    amount: Option<Uint128>,
+    recipient: Option<Addr>,
+  },
+  WithdrawAll {
+    recipient: Option<Addr>,
+  },
+}
+
+/// Message embedded in a CW20 Send to this contract.
+#[cw_serde]
+pub enum ReceiveMsg {
+  Submit(OrderRequest),
+  Deposit {},
 }
 
 #[cw_serde]
@@ -36,15 +104,35 @@ pub enum QueryMsg {
     limit: Option<u8>,
     cursor: Option<Uint64>,
   },
+  Order {
+    account: Addr,
+    order_id: Option<Uint64>,
+    client_order_id: Option<String>,
+  },
+  Depth {
+    quote: Token,
+    limit: Option<u8>,
+  },
+  Dust {},
+  MarketConfig {
+    quote: Token,
+  },
 }
 
 #[cw_serde]
 pub struct MigrateMsg {}
 
+#[cw_serde]
+pub struct BalanceView {
+  pub token: Token,
+  pub free: Uint128,
+  pub locked: Uint128,
+}
+
 #[cw_serde]
 pub struct AccountView {
-  pub base_balance: Uint128,
-  pub quote_balances: Vec<TokenAmount>,
+  pub base_balance: BalanceView,
+  pub quote_balances: Vec<BalanceView>,
 }
 
 #[cw_serde]
@@ -53,6 +141,49 @@ pub struct OrdersResponse {
   pub cursor: Option<Uint64>,
 }
 
+/// Amounts traded by a market sell, serialized in the Submit response data.
+#[cw_serde]
+pub struct MarketSellResult {
+  pub order_id: Uint64,
+  pub qty_sold: Uint128,
+  pub quote_received: Uint128,
+}
+
+/// Result of one order in a batch, serialized in the SubmitBatch response data.
+#[cw_serde]
+pub struct BatchOrderResult {
+  pub order_id: Option<Uint64>,
+  pub status: Option<OrderStatus>,
+  pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct DepthLevel {
+  pub price: Decimal256,
+  pub qty: Uint128,
+}
+
+#[cw_serde]
+pub struct DepthResponse {
+  pub bids: Vec<DepthLevel>,
+  pub asks: Vec<DepthLevel>,
+}
+
+#[cw_serde]
+pub struct MarketConfigResponse {
+  pub quote: Token,
+  pub price_precision: u8,
+  pub tick_size: Decimal256,
+  pub lot_size: Uint128,
+  pub min_qty: Uint128,
+  pub min_notional: Uint128,
+}
+
+#[cw_serde]
+pub struct DustResponse {
+  pub dust: Vec<TokenAmount>,
+}
+
 #[cw_serde]
 pub struct SelectResponse {
   pub account: Option<AccountView>,
@@ -64,22 +195,145 @@ pub enum OrderRequest {
     quote: Token,
     balance: Uint128,
     tif: TimeInForce,
+    /// Highest price at which to match, protecting against slippage.
+    max_price: Option<Decimal256>,
+    /// Least qty of base token that must be bought.
+    min_receive: Option<Uint128>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
+  },
+  /// Buy an exact qty of base token, spending no more than the max spend.
+  MarketBuyQty {
+    quote: Token,
+    qty: Uint128,
+    max_spend: Uint128,
+    tif: TimeInForce,
+    /// Highest price at which to match, protecting against slippage.
+    max_price: Option<Decimal256>,
+    /// Least qty of base token that must be bought.
+    min_receive: Option<Uint128>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
   },
   MarketSell {
     quote: Token,
     qty: Uint128,
     tif: TimeInForce,
+    /// Lowest price at which to match, protecting against slippage.
+    min_price: Option<Decimal256>,
+    /// Least amount of quote token that must be received.
+    min_receive: Option<Uint128>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
+  },
+  /// Sell enough base token to raise the given quote amount, selling no more
+  /// than the max qty.
+  MarketSellQuote {
+    quote: Token,
+    amount: Uint128,
+    max_qty: Uint128,
+    tif: TimeInForce,
+    /// Lowest price at which to match, protecting against slippage.
+    min_price: Option<Decimal256>,
+    /// Least amount of quote token that must be received.
+    min_receive: Option<Uint128>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
   },
   LimitBuy {
     quote: Token,
     qty: Uint128,
-    price: Uint128,
+    price: Decimal256,
     tif: TimeInForce,
+    post_only: Option<PostOnly>,
+    /// Qty shown in the book at a time, hiding the rest in reserve.
+    display_qty: Option<Uint128>,
+    /// Least qty that may be matched against the order at once.
+    min_fill_qty: Option<Uint128>,
+    /// Match only in full, whether on arrival or resting in the book.
+    all_or_none: Option<bool>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
   },
   LimitSell {
     quote: Token,
     qty: Uint128,
-    price: Uint128,
+    price: Decimal256,
+    tif: TimeInForce,
+    post_only: Option<PostOnly>,
+    /// Qty shown in the book at a time, hiding the rest in reserve.
+    display_qty: Option<Uint128>,
+    /// Least qty that may be matched against the order at once.
+    min_fill_qty: Option<Uint128>,
+    /// Match only in full, whether on arrival or resting in the book.
+    all_or_none: Option<bool>,
+    /// Handling of matches against the owner's own resting orders.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    client_order_id: Option<String>,
+  },
+  StopMarket {
+    quote: Token,
+    side: OrderSide,
+    trigger_price: Decimal256,
+    /// Quote balance to spend when buying or base qty to sell when selling.
+    amount: Uint128,
+    tif: TimeInForce,
+    client_order_id: Option<String>,
+  },
+  StopLimit {
+    quote: Token,
+    side: OrderSide,
+    trigger_price: Decimal256,
+    qty: Uint128,
+    price: Decimal256,
+    tif: TimeInForce,
+    client_order_id: Option<String>,
+  },
+  TrailingStop {
+    quote: Token,
+    side: OrderSide,
+    offset: TrailingOffset,
+    /// Quote balance to spend when buying or base qty to sell when selling.
+    amount: Uint128,
     tif: TimeInForce,
+    client_order_id: Option<String>,
   },
 }
+
+impl OrderRequest {
+  /// Get the owner-assigned ID of the requested order, which must be unique
+  /// among the owner's orders.
+  pub fn get_client_order_id(&self) -> Option<&String> {
+    match self {
+      OrderRequest::MarketBuy { client_order_id, .. }
+      | OrderRequest::MarketBuyQty { client_order_id, .. }
+      | OrderRequest::MarketSell { client_order_id, .. }
+      | OrderRequest::MarketSellQuote { client_order_id, .. }
+      | OrderRequest::LimitBuy { client_order_id, .. }
+      | OrderRequest::LimitSell { client_order_id, .. }
+      | OrderRequest::StopMarket { client_order_id, .. }
+      | OrderRequest::StopLimit { client_order_id, .. }
+      | OrderRequest::TrailingStop { client_order_id, .. } => client_order_id.as_ref(),
+    }
+  }
+
+  /// Get the quote token of the market the order is for.
+  pub fn get_quote(&self) -> &Token {
+    match self {
+      OrderRequest::MarketBuy { quote, .. }
+      | OrderRequest::MarketBuyQty { quote, .. }
+      | OrderRequest::MarketSell { quote, .. }
+      | OrderRequest::MarketSellQuote { quote, .. }
+      | OrderRequest::LimitBuy { quote, .. }
+      | OrderRequest::LimitSell { quote, .. }
+      | OrderRequest::StopMarket { quote, .. }
+      | OrderRequest::StopLimit { quote, .. }
+      | OrderRequest::TrailingStop { quote, .. } => quote,
+    }
+  }
+}
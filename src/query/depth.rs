@@ -0,0 +1,71 @@
+use std::marker::PhantomData;
+
+use crate::{
+  error::ContractError,
+  models::OrderId,
+  msg::{DepthLevel, DepthResponse},
+  state::{from_price_units, load_token_id, ASKS, BIDS, BOOK_ORDER_IDS, ORDERS},
+};
+use cosmwasm_std::{BlockInfo, Deps, Env, Order, Storage};
+use cw_lib::models::Token;
+use cw_storage_plus::{Map, PrefixBound};
+
+/// Get the total qty shown in the book at each of the best bid and ask prices.
+/// Only the displayed slice of each iceberg order is counted.
+pub fn depth(
+  deps: Deps,
+  env: Env,
+  quote: Token,
+  maybe_limit: Option<u8>,
+) -> Result<DepthResponse, ContractError> {
+  let quote_token_id = load_token_id(deps.storage, &quote)?;
+  let limit = maybe_limit.unwrap_or(20).clamp(1, 50) as usize;
+  Ok(DepthResponse {
+    bids: load_levels(
+      deps.storage,
+      &env.block,
+      &BIDS,
+      quote_token_id,
+      Order::Descending,
+      limit,
+    )?,
+    asks: load_levels(deps.storage, &env.block, &ASKS, quote_token_id, Order::Ascending, limit)?,
+  })
+}
+
+fn load_levels(
+  storage: &dyn Storage,
+  block: &BlockInfo,
+  map: &Map<(u32, u128, OrderId), u8>,
+  quote_token_id: u32,
+  order: Order,
+  limit: usize,
+) -> Result<Vec<DepthLevel>, ContractError> {
+  let mut levels: Vec<DepthLevel> = Vec::with_capacity(limit);
+  for result in map.prefix_range(
+    storage,
+    Some(PrefixBound::Inclusive(((quote_token_id, u128::MIN), PhantomData))),
+    Some(PrefixBound::Inclusive(((quote_token_id, u128::MAX), PhantomData))),
+    order,
+  ) {
+    let ((_, price, book_id), _) = result?;
+    let order_id = BOOK_ORDER_IDS.may_load(storage, book_id)?.unwrap_or(book_id);
+    let book_order = ORDERS.load(storage, order_id)?;
+
+    // Expired orders are only removed from the book lazily, while matching.
+    if book_order.is_expired(block) {
+      continue;
+    }
+
+    let price = from_price_units(storage, quote_token_id, price.into())?;
+    let qty = book_order.get_qty_visible();
+    if let Some(level) = levels.last_mut().filter(|level| level.price == price) {
+      level.qty += qty;
+    } else if levels.len() == limit {
+      break;
+    } else {
+      levels.push(DepthLevel { price, qty });
+    }
+  }
+  Ok(levels)
+}
@@ -0,0 +1,20 @@
+use crate::{
+  error::ContractError,
+  msg::DustResponse,
+  state::{load_token_by_id, DUST_BALANCES},
+};
+use cosmwasm_std::{Deps, Order};
+use cw_lib::models::TokenAmount;
+
+/// Get the dust collected from rounding market buys, per quote token.
+pub fn dust(deps: Deps) -> Result<DustResponse, ContractError> {
+  let mut dust: Vec<TokenAmount> = vec![];
+  for result in DUST_BALANCES.range(deps.storage, None, None, Order::Ascending) {
+    let (token_id, amount) = result?;
+    dust.push(TokenAmount {
+      token: load_token_by_id(deps.storage, token_id)?,
+      amount,
+    });
+  }
+  Ok(DustResponse { dust })
+}
@@ -0,0 +1,24 @@
+use crate::{
+  error::ContractError,
+  msg::MarketConfigResponse,
+  state::{from_price_units, load_market_config, load_token_id},
+};
+use cosmwasm_std::Deps;
+use cw_lib::models::Token;
+
+/// Get the price precision and trading rules of the market for a quote token.
+pub fn market_config(
+  deps: Deps,
+  quote: Token,
+) -> Result<MarketConfigResponse, ContractError> {
+  let quote_token_id = load_token_id(deps.storage, &quote)?;
+  let config = load_market_config(deps.storage, quote_token_id)?;
+  Ok(MarketConfigResponse {
+    quote,
+    price_precision: config.price_precision,
+    tick_size: from_price_units(deps.storage, quote_token_id, config.tick_size)?,
+    lot_size: config.lot_size,
+    min_qty: config.min_qty,
+    min_notional: config.min_notional,
+  })
+}
@@ -1,5 +1,11 @@
+mod depth;
+mod dust;
+mod market;
 mod orders;
 mod select;
 
-pub use orders::orders;
+pub use depth::depth;
+pub use dust::dust;
+pub use market::market_config;
+pub use orders::{order, orders};
 pub use select::select;
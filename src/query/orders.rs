@@ -2,26 +2,44 @@ use std::marker::PhantomData;
 
 use crate::{
   error::ContractError,
-  models::Order,
+  models::{Order, OrderStatus},
   msg::OrdersResponse,
-  state::{ACCOUNT_ORDER_IDS, ORDERS},
+  state::{resolve_order_id, ACCOUNT_ORDER_IDS, ORDERS},
 };
-use cosmwasm_std::{Addr, Deps, Uint64};
+use cosmwasm_std::{Addr, Deps, Env, Uint64};
 use cw_storage_plus::Bound;
 
+/// Get one of an account's orders, by its ID or its owner-assigned ID.
+pub fn order(
+  deps: Deps,
+  env: Env,
+  account: Addr,
+  maybe_order_id: Option<Uint64>,
+  maybe_client_order_id: Option<String>,
+) -> Result<Order, ContractError> {
+  let order_id = resolve_order_id(deps.storage, &account, maybe_order_id, maybe_client_order_id)?;
+  let mut order = ORDERS
+    .may_load(deps.storage, order_id)?
+    .filter(|order| order.owner == account)
+    .ok_or(ContractError::OrderNotFound)?;
+  order.id = Some(order_id.into());
+  // Expired orders are only removed from the book lazily, while matching.
+  if order.is_open() && order.is_expired(&env.block) {
+    order.status = OrderStatus::Expired.into();
+  }
+  Ok(order)
+}
+
 pub fn orders(
   deps: Deps,
+  env: Env,
   account: Addr,
   maybe_cursor: Option<Uint64>,
   maybe_limit: Option<u8>,
 ) -> Result<OrdersResponse, ContractError> {
   let mut orders: Vec<Order> = Vec::with_capacity(20);
 
-  let start_bound = if let Some(cursor) = maybe_cursor {
-    Some(Bound::Exclusive((cursor.u64(), PhantomData)))
-  } else {
-    None
-  };
+  let start_bound = maybe_cursor.map(|cursor| Bound::Exclusive((cursor.u64(), PhantomData)));
 
   for result in ACCOUNT_ORDER_IDS
     .prefix(&account)
@@ -31,6 +49,9 @@ pub fn orders(
     let order_id = result?;
     let mut order = ORDERS.load(deps.storage, order_id)?;
     order.id = Some(order_id.into());
+    if order.is_open() && order.is_expired(&env.block) {
+      order.status = OrderStatus::Expired.into();
+    }
     orders.push(order)
   }
 
@@ -1,10 +1,10 @@
 use crate::{
   error::ContractError,
-  msg::{AccountView, SelectResponse},
-  state::{load_token_amount, load_token_by_id, BASE_TOKEN, BASE_TOKEN_ID, TOKEN_BALANCES},
+  msg::{AccountView, BalanceView, SelectResponse},
+  state::{BASE_TOKEN, BASE_TOKEN_ID, LOCKED_BALANCES, TOKENS, TOKEN_BALANCES},
 };
 use cosmwasm_std::{Addr, Deps, Storage};
-use cw_lib::{loader::StateLoader, models::TokenAmount};
+use cw_lib::{loader::StateLoader, models::Token};
 
 pub fn select(
   deps: Deps,
@@ -16,27 +16,39 @@ pub fn select(
   Ok(SelectResponse {
     account: loader.view("account", |account_addr| {
       Ok(Some(AccountView {
-        base_balance: load_token_amount(deps.storage, &account_addr, &base_token)?.amount,
+        base_balance: load_balance(deps.storage, &account_addr, BASE_TOKEN_ID, &base_token)?,
         quote_balances: load_quote_balances(deps.storage, &account_addr)?,
       }))
     })?,
   })
 }
 
+fn load_balance(
+  storage: &dyn Storage,
+  owner: &Addr,
+  token_id: u32,
+  token: &Token,
+) -> Result<BalanceView, ContractError> {
+  Ok(BalanceView {
+    token: token.clone(),
+    free: TOKEN_BALANCES.may_load(storage, (owner, token_id))?.unwrap_or_default(),
+    locked: LOCKED_BALANCES
+      .may_load(storage, (owner, token_id))?
+      .unwrap_or_default(),
+  })
+}
+
 fn load_quote_balances(
   storage: &dyn Storage,
   owner: &Addr,
-) -> Result<Vec<TokenAmount>, ContractError> {
-  let mut token_amounts: Vec<TokenAmount> = Vec::with_capacity(2);
-  for result in TOKEN_BALANCES
-    .prefix(owner)
-    .range(storage, None, None, cosmwasm_std::Order::Ascending)
-  {
-    let (token_id, balance) = result?;
-    if token_id != BASE_TOKEN_ID {
-      let token = load_token_by_id(storage, token_id)?;
-      token_amounts.push(TokenAmount { token, amount: balance })
+) -> Result<Vec<BalanceView>, ContractError> {
+  let mut balances: Vec<BalanceView> = Vec::with_capacity(2);
+  for result in TOKENS.range(storage, None, None, cosmwasm_std::Order::Ascending) {
+    let (token_id, token) = result?;
+    let balance = load_balance(storage, owner, token_id, &token)?;
+    if !(balance.free.is_zero() && balance.locked.is_zero()) {
+      balances.push(balance)
     }
   }
-  Ok(token_amounts)
+  Ok(balances)
 }
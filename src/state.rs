@@ -1,10 +1,12 @@
-use crate::msg::InstantiateMsg;
+use crate::msg::{InstantiateMsg, QuoteTokenInitArgs};
 use crate::utils::increment;
 use crate::{
   error::ContractError,
-  models::{Order, OrderId},
+  models::{Bracket, DustPolicy, MarketConfig, Order, OrderId, TrailingStopPass},
+};
+use cosmwasm_std::{
+  to_binary, Addr, Decimal256, DepsMut, Env, MessageInfo, Response, Storage, SubMsg, Uint128, Uint256, Uint64, WasmMsg,
 };
-use cosmwasm_std::{to_binary, Addr, DepsMut, Env, MessageInfo, Response, Storage, SubMsg, Uint128, Uint64, WasmMsg};
 use cw20::{Cw20Coin, MinterResponse};
 use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
 use cw_lib::models::{Token, TokenAmount};
@@ -12,17 +14,40 @@ use cw_storage_plus::{Item, Map};
 
 pub const CW20_INSTANTIATE_MSG_REPLY_ID: u64 = 1;
 pub const BASE_TOKEN_ID: u32 = 1;
+/// Max number of decimal places in a price, as allowed by Decimal256.
+pub const MAX_PRICE_PRECISION: u8 = 18;
 
 pub const BASE_TOKEN: Item<Token> = Item::new("base_token");
 pub const TOKEN_ID_SEQ_NO: Item<u32> = Item::new("token_id_seq_no");
 pub const ORDER_ID_SEQ_NO: Item<Uint64> = Item::new("order_id_seq_no");
+pub const DUST_POLICY: Item<DustPolicy> = Item::new("dust_policy");
 pub const TOKEN_IDS: Map<String, u32> = Map::new("token_ids");
 pub const TOKENS: Map<u32, Token> = Map::new("tokens");
+pub const MARKET_CONFIGS: Map<u32, MarketConfig> = Map::new("market_configs");
 pub const TOKEN_BALANCES: Map<(&Addr, u32), Uint128> = Map::new("token_balances");
+pub const LOCKED_BALANCES: Map<(&Addr, u32), Uint128> = Map::new("locked_balances");
 pub const ORDERS: Map<OrderId, Order> = Map::new("orders");
+pub const ORDER_GROUP_IDS: Map<OrderId, OrderId> = Map::new("order_group_ids");
+pub const GROUP_ORDER_IDS: Map<(OrderId, OrderId), u8> = Map::new("group_order_ids");
+pub const BRACKETS: Map<OrderId, Bracket> = Map::new("brackets");
+pub const BRACKET_FILLS: Map<OrderId, Uint128> = Map::new("bracket_fills");
 pub const ACCOUNT_ORDER_IDS: Map<(&Addr, OrderId), u8> = Map::new("account_order_ids");
+pub const CLIENT_ORDER_IDS: Map<(&Addr, &str), OrderId> = Map::new("client_order_ids");
 pub const ASKS: Map<(u32, u128, OrderId), u8> = Map::new("asks");
 pub const BIDS: Map<(u32, u128, OrderId), u8> = Map::new("bids");
+pub const BOOK_ORDER_IDS: Map<OrderId, OrderId> = Map::new("book_order_ids");
+pub const BUY_TRIGGERS: Map<(u32, u128, OrderId), u8> = Map::new("buy_triggers");
+pub const SELL_TRIGGERS: Map<(u32, u128, OrderId), u8> = Map::new("sell_triggers");
+pub const BUY_TRAILING_STOPS: Map<(u32, u128, OrderId), u8> = Map::new("buy_trailing_stops");
+pub const SELL_TRAILING_STOPS: Map<(u32, u128, OrderId), u8> = Map::new("sell_trailing_stops");
+/// Extreme prices traded in each market, by the last order ID placed before
+/// they traded, that some trailing stops may not have been ratcheted to yet.
+pub const BUY_TRAILING_EXTREMES: Map<(u32, OrderId), Uint128> = Map::new("buy_trailing_extremes");
+pub const SELL_TRAILING_EXTREMES: Map<(u32, OrderId), Uint128> = Map::new("sell_trailing_extremes");
+pub const BUY_TRAILING_PASSES: Map<u32, TrailingStopPass> = Map::new("buy_trailing_passes");
+pub const SELL_TRAILING_PASSES: Map<u32, TrailingStopPass> = Map::new("sell_trailing_passes");
+pub const LAST_PRICES: Map<u32, Uint128> = Map::new("last_prices");
+pub const DUST_BALANCES: Map<u32, Uint128> = Map::new("dust_balances");
 
 /// Initialize contract state data.
 pub fn initialize(
@@ -33,9 +58,16 @@ pub fn initialize(
 ) -> Result<Response, ContractError> {
   ORDER_ID_SEQ_NO.save(deps.storage, &Uint64::zero())?;
   TOKEN_ID_SEQ_NO.save(deps.storage, &BASE_TOKEN_ID)?;
+  let dust_policy = match &msg.dust_policy {
+    Some(DustPolicy::Collect { recipient }) => DustPolicy::Collect {
+      recipient: deps.api.addr_validate(recipient.as_str())?,
+    },
+    _ => DustPolicy::Refund,
+  };
+  DUST_POLICY.save(deps.storage, &dust_policy)?;
 
-  for token in msg.quote_tokens.iter() {
-    register_token(deps.storage, token, None)?;
+  for quote_token in msg.quote_tokens.iter() {
+    register_quote_token(deps.storage, quote_token)?;
   }
 
   Ok(
@@ -90,7 +122,9 @@ pub fn load_token_by_id(
   storage: &dyn Storage,
   token_id: u32,
 ) -> Result<Token, ContractError> {
-  if let Some(token) = TOKENS.may_load(storage, token_id)? {
+  if token_id == BASE_TOKEN_ID {
+    Ok(BASE_TOKEN.load(storage)?)
+  } else if let Some(token) = TOKENS.may_load(storage, token_id)? {
     Ok(token)
   } else {
     Err(ContractError::TokenNotFound)
@@ -124,3 +158,197 @@ pub fn register_token(
   }
   Ok(token_id)
 }
+
+/// Register a token to trade the base token against, along with the config of
+/// its market.
+pub fn register_quote_token(
+  storage: &mut dyn Storage,
+  quote_token: &QuoteTokenInitArgs,
+) -> Result<u32, ContractError> {
+  let price_precision = quote_token.price_precision;
+  if price_precision > MAX_PRICE_PRECISION {
+    return Err(ContractError::InvalidPricePrecision);
+  }
+  let defaults = MarketConfig::default();
+  let config = MarketConfig {
+    price_precision,
+    tick_size: match quote_token.tick_size {
+      Some(tick_size) => scale_price(tick_size, price_precision)?,
+      None => defaults.tick_size,
+    },
+    lot_size: quote_token.lot_size.unwrap_or(defaults.lot_size),
+    min_qty: quote_token.min_qty.unwrap_or(defaults.min_qty),
+    min_notional: quote_token.min_notional.unwrap_or(defaults.min_notional),
+  };
+  if config.tick_size.is_zero() || config.lot_size.is_zero() {
+    return Err(ContractError::InvalidMarketConfig);
+  }
+  let token_id = register_token(storage, &quote_token.token, None)?;
+  MARKET_CONFIGS.save(storage, token_id, &config)?;
+  Ok(token_id)
+}
+
+/// Get the config of the market for the given quote token. Tokens registered
+/// without one trade in whole price units and lots, with no minimums.
+pub fn load_market_config(
+  storage: &dyn Storage,
+  token_id: u32,
+) -> Result<MarketConfig, ContractError> {
+  Ok(MARKET_CONFIGS.may_load(storage, token_id)?.unwrap_or_default())
+}
+
+/// Get the number of price units per unit of a quote token. Prices are stored
+/// as integers in these units, so quote amounts are `qty * price / scale`.
+pub fn load_price_scale(
+  storage: &dyn Storage,
+  token_id: u32,
+) -> Result<Uint128, ContractError> {
+  let precision = load_market_config(storage, token_id)?.price_precision;
+  Ok(Uint128::from(10u128.pow(precision.into())))
+}
+
+/// Convert a decimal price into the price units of the given quote token. The
+/// price can't have more decimal places than the token's price precision.
+pub fn to_price_units(
+  storage: &dyn Storage,
+  token_id: u32,
+  price: Decimal256,
+) -> Result<Uint128, ContractError> {
+  scale_price(price, load_market_config(storage, token_id)?.price_precision)
+}
+
+fn scale_price(
+  price: Decimal256,
+  precision: u8,
+) -> Result<Uint128, ContractError> {
+  let divisor = Uint256::from(10u128.pow(Decimal256::DECIMAL_PLACES - u32::from(precision)));
+  if !(price.atomics() % divisor).is_zero() {
+    return Err(ContractError::InvalidPrice);
+  }
+  Uint128::try_from(price.atomics() / divisor).map_err(|_| ContractError::InvalidPrice)
+}
+
+/// Convert a price in the price units of the given quote token into a decimal.
+pub fn from_price_units(
+  storage: &dyn Storage,
+  token_id: u32,
+  price: Uint128,
+) -> Result<Decimal256, ContractError> {
+  Ok(Decimal256::from_ratio(price, load_price_scale(storage, token_id)?))
+}
+
+pub fn increment_token_balance(
+  storage: &mut dyn Storage,
+  addr: &Addr,
+  token_id: u32,
+  delta: Uint128,
+) -> Result<(), ContractError> {
+  TOKEN_BALANCES.update(storage, (addr, token_id), |maybe_balance| -> Result<_, ContractError> {
+    let balance = maybe_balance.unwrap_or_default();
+    Ok(balance + delta)
+  })?;
+  Ok(())
+}
+
+/// Credit dust collected from a market buy in the given quote token to the
+/// recipient, adding it to the total dust collected in the token.
+pub fn collect_dust(
+  storage: &mut dyn Storage,
+  recipient: &Addr,
+  token_id: u32,
+  delta: Uint128,
+) -> Result<(), ContractError> {
+  increment_token_balance(storage, recipient, token_id, delta)?;
+  DUST_BALANCES.update(storage, token_id, |maybe_balance| -> Result<_, ContractError> {
+    let balance = maybe_balance.unwrap_or_default();
+    Ok(balance + delta)
+  })?;
+  Ok(())
+}
+
+pub fn decrement_token_balance(
+  storage: &mut dyn Storage,
+  addr: &Addr,
+  token_id: u32,
+  delta: Uint128,
+) -> Result<(), ContractError> {
+  let balance = TOKEN_BALANCES.may_load(storage, (addr, token_id))?.unwrap_or_default();
+  if balance < delta {
+    return Err(ContractError::InsufficientBalance);
+  }
+  if balance == delta {
+    TOKEN_BALANCES.remove(storage, (addr, token_id));
+  } else {
+    TOKEN_BALANCES.save(storage, (addr, token_id), &(balance - delta))?;
+  }
+  Ok(())
+}
+
+pub fn increment_locked_balance(
+  storage: &mut dyn Storage,
+  addr: &Addr,
+  token_id: u32,
+  delta: Uint128,
+) -> Result<(), ContractError> {
+  LOCKED_BALANCES.update(storage, (addr, token_id), |maybe_balance| -> Result<_, ContractError> {
+    let balance = maybe_balance.unwrap_or_default();
+    Ok(balance + delta)
+  })?;
+  Ok(())
+}
+
+pub fn decrement_locked_balance(
+  storage: &mut dyn Storage,
+  addr: &Addr,
+  token_id: u32,
+  delta: Uint128,
+) -> Result<(), ContractError> {
+  let balance = LOCKED_BALANCES.may_load(storage, (addr, token_id))?.unwrap_or_default();
+  if balance < delta {
+    return Err(ContractError::InsufficientBalance);
+  }
+  if balance == delta {
+    LOCKED_BALANCES.remove(storage, (addr, token_id));
+  } else {
+    LOCKED_BALANCES.save(storage, (addr, token_id), &(balance - delta))?;
+  }
+  Ok(())
+}
+
+/// Max length of an owner-assigned order ID.
+const MAX_CLIENT_ORDER_ID_LEN: usize = 64;
+
+/// Index an order by its owner-assigned ID, which may only ever be used once
+/// per owner, so that resubmitting the same order is rejected.
+pub fn save_client_order_id(
+  storage: &mut dyn Storage,
+  owner: &Addr,
+  client_order_id: &str,
+  order_id: OrderId,
+) -> Result<(), ContractError> {
+  if client_order_id.is_empty() || client_order_id.len() > MAX_CLIENT_ORDER_ID_LEN {
+    return Err(ContractError::InvalidClientOrderId);
+  }
+  if CLIENT_ORDER_IDS.has(storage, (owner, client_order_id)) {
+    return Err(ContractError::DuplicateClientOrderId);
+  }
+  CLIENT_ORDER_IDS.save(storage, (owner, client_order_id), &order_id)?;
+  Ok(())
+}
+
+/// Get the ID of an order referenced either by its ID or by the owner-assigned
+/// ID given to it by the owner. Exactly one of the two must be given.
+pub fn resolve_order_id(
+  storage: &dyn Storage,
+  owner: &Addr,
+  maybe_order_id: Option<Uint64>,
+  maybe_client_order_id: Option<String>,
+) -> Result<OrderId, ContractError> {
+  match (maybe_order_id, maybe_client_order_id) {
+    (Some(order_id), None) => Ok(order_id.u64()),
+    (None, Some(client_order_id)) => CLIENT_ORDER_IDS
+      .may_load(storage, (owner, &client_order_id))?
+      .ok_or(ContractError::OrderNotFound),
+    _ => Err(ContractError::InvalidOrderId),
+  }
+}
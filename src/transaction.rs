@@ -0,0 +1,263 @@
+use std::{
+  cmp::Ordering,
+  collections::BTreeMap,
+  iter::Peekable,
+  ops::Bound::{Excluded, Included, Unbounded},
+};
+
+use cosmwasm_std::{Order, Record, Storage};
+
+/// Iterator over buffered writes, where None marks a removed key.
+type WritesIter<'b> = Box<dyn Iterator<Item = (&'b Vec<u8>, &'b Option<Vec<u8>>)> + 'b>;
+
+/// Storage that buffers writes on top of another storage until committed, so
+/// that a failed operation can be discarded without affecting the rest of the
+/// message. Dropping the transaction without committing discards its writes.
+pub struct StorageTransaction<'a> {
+  storage: &'a mut dyn Storage,
+  /// Buffered writes, where None marks a removed key.
+  writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> StorageTransaction<'a> {
+  pub fn new(storage: &'a mut dyn Storage) -> Self {
+    Self {
+      storage,
+      writes: BTreeMap::new(),
+    }
+  }
+
+  /// Apply all buffered writes to the underlying storage.
+  pub fn commit(self) {
+    for (key, maybe_value) in self.writes {
+      if let Some(value) = maybe_value {
+        self.storage.set(&key, &value);
+      } else {
+        self.storage.remove(&key);
+      }
+    }
+  }
+}
+
+impl<'a> Storage for StorageTransaction<'a> {
+  fn get(
+    &self,
+    key: &[u8],
+  ) -> Option<Vec<u8>> {
+    match self.writes.get(key) {
+      Some(maybe_value) => maybe_value.clone(),
+      None => self.storage.get(key),
+    }
+  }
+
+  fn range<'b>(
+    &'b self,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+  ) -> Box<dyn Iterator<Item = Record> + 'b> {
+    let bounds = (
+      start.map_or(Unbounded, |start| Included(start.to_vec())),
+      end.map_or(Unbounded, |end| Excluded(end.to_vec())),
+    );
+    // A range whose end precedes its start is empty, but BTreeMap panics.
+    if matches!((start, end), (Some(start), Some(end)) if start > end) {
+      return Box::new(std::iter::empty());
+    }
+    let writes: WritesIter<'b> = match order {
+      Order::Ascending => Box::new(self.writes.range(bounds)),
+      Order::Descending => Box::new(self.writes.range(bounds).rev()),
+    };
+    Box::new(MergedRange {
+      base: self.storage.range(start, end, order).peekable(),
+      writes: writes.peekable(),
+      order,
+    })
+  }
+
+  fn set(
+    &mut self,
+    key: &[u8],
+    value: &[u8],
+  ) {
+    self.writes.insert(key.to_vec(), Some(value.to_vec()));
+  }
+
+  fn remove(
+    &mut self,
+    key: &[u8],
+  ) {
+    self.writes.insert(key.to_vec(), None);
+  }
+}
+
+/// Records in the underlying storage merged with buffered writes, in order.
+struct MergedRange<'b> {
+  base: Peekable<Box<dyn Iterator<Item = Record> + 'b>>,
+  writes: Peekable<WritesIter<'b>>,
+  order: Order,
+}
+
+impl<'b> Iterator for MergedRange<'b> {
+  type Item = Record;
+
+  fn next(&mut self) -> Option<Record> {
+    loop {
+      let ordering = match (self.base.peek(), self.writes.peek()) {
+        (None, None) => return None,
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some((base_key, _)), Some((write_key, _))) => match self.order {
+          Order::Ascending => base_key.cmp(*write_key),
+          Order::Descending => (*write_key).cmp(base_key),
+        },
+      };
+      if ordering == Ordering::Less {
+        return self.base.next();
+      }
+      // A buffered write shadows the same key in the underlying storage.
+      if ordering == Ordering::Equal {
+        self.base.next();
+      }
+      if let Some((key, Some(value))) = self.writes.next() {
+        return Some((key.clone(), value.clone()));
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cosmwasm_std::testing::MockStorage;
+
+  use super::*;
+
+  /// Underlying storage with keys a, c and e.
+  fn base_storage() -> MockStorage {
+    let mut storage = MockStorage::new();
+    for key in [b"a", b"c", b"e"] {
+      storage.set(key, b"base");
+    }
+    storage
+  }
+
+  fn collect(
+    storage: &dyn Storage,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+  ) -> Vec<(String, String)> {
+    storage
+      .range(start, end, order)
+      .map(|(key, value)| (String::from_utf8(key).unwrap(), String::from_utf8(value).unwrap()))
+      .collect()
+  }
+
+  fn records(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+    entries
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn writes_shadow_underlying_keys() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"a", b"tx");
+    tx.set(b"b", b"tx");
+    tx.remove(b"c");
+    tx.remove(b"d");
+
+    assert_eq!(tx.get(b"a"), Some(b"tx".to_vec()));
+    assert_eq!(tx.get(b"b"), Some(b"tx".to_vec()));
+    assert_eq!(tx.get(b"c"), None);
+    assert_eq!(tx.get(b"e"), Some(b"base".to_vec()));
+  }
+
+  #[test]
+  fn range_merges_writes_in_both_orders() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"a", b"tx");
+    tx.set(b"b", b"tx");
+    tx.remove(b"c");
+    tx.set(b"f", b"tx");
+
+    let expected = records(&[("a", "tx"), ("b", "tx"), ("e", "base"), ("f", "tx")]);
+    assert_eq!(collect(&tx, None, None, Order::Ascending), expected);
+    let reversed: Vec<_> = expected.into_iter().rev().collect();
+    assert_eq!(collect(&tx, None, None, Order::Descending), reversed);
+  }
+
+  #[test]
+  fn range_respects_bounds() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"b", b"tx");
+    tx.set(b"d", b"tx");
+    tx.remove(b"e");
+
+    // The start bound is inclusive and the end bound exclusive.
+    assert_eq!(
+      collect(&tx, Some(b"b"), Some(b"e"), Order::Ascending),
+      records(&[("b", "tx"), ("c", "base"), ("d", "tx")]),
+    );
+    assert_eq!(
+      collect(&tx, Some(b"b"), Some(b"e"), Order::Descending),
+      records(&[("d", "tx"), ("c", "base"), ("b", "tx")]),
+    );
+  }
+
+  #[test]
+  fn empty_and_inverted_ranges_are_empty() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"b", b"tx");
+
+    for order in [Order::Ascending, Order::Descending] {
+      assert!(collect(&tx, Some(b"b"), Some(b"b"), order).is_empty());
+      assert!(collect(&tx, Some(b"d"), Some(b"b"), order).is_empty());
+    }
+  }
+
+  #[test]
+  fn range_skips_removed_keys_at_the_ends() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.remove(b"a");
+    tx.remove(b"e");
+
+    assert_eq!(collect(&tx, None, None, Order::Ascending), records(&[("c", "base")]));
+    assert_eq!(collect(&tx, None, None, Order::Descending), records(&[("c", "base")]));
+  }
+
+  #[test]
+  fn commit_applies_writes() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"a", b"tx");
+    tx.set(b"b", b"tx");
+    tx.remove(b"c");
+    tx.commit();
+
+    assert_eq!(
+      collect(&base, None, None, Order::Ascending),
+      records(&[("a", "tx"), ("b", "tx"), ("e", "base")]),
+    );
+  }
+
+  #[test]
+  fn dropping_discards_writes() {
+    let mut base = base_storage();
+    let mut tx = StorageTransaction::new(&mut base);
+    tx.set(b"a", b"tx");
+    tx.remove(b"c");
+    drop(tx);
+
+    assert_eq!(
+      collect(&base, None, None, Order::Ascending),
+      records(&[("a", "base"), ("c", "base"), ("e", "base")]),
+    );
+  }
+}
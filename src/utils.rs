@@ -1,4 +1,6 @@
-use cosmwasm_std::{Storage, Uint128};
+use cosmwasm_std::{to_binary, Addr, BankMsg, Coin, CosmosMsg, Storage, Uint128, WasmMsg};
+use cw20::Cw20ExecuteMsg;
+use cw_lib::models::Token;
 use cw_storage_plus::Item;
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -36,3 +38,46 @@ pub fn mul_pct(
 ) -> Uint128 {
   total.multiply_ratio(pct, Uint128::from(1_000_000u128))
 }
+
+/// Get the quote amount of a qty at a price in price units, of which there are
+/// `price_scale` per unit of quote token, rounded down.
+pub fn mul_price(
+  qty: Uint128,
+  price: Uint128,
+  price_scale: Uint128,
+) -> Uint128 {
+  qty.multiply_ratio(price, price_scale)
+}
+
+/// Get the quote amount of a qty at a price in price units, like `mul_price`,
+/// but fail if the amount overflows instead of panicking.
+pub fn checked_mul_price(
+  qty: Uint128,
+  price: Uint128,
+  price_scale: Uint128,
+) -> Result<Uint128, ContractError> {
+  qty
+    .checked_multiply_ratio(price, price_scale)
+    .map_err(|_| ContractError::QuoteAmountOverflow)
+}
+
+pub fn build_transfer_msg(
+  token: &Token,
+  recipient: &Addr,
+  amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+  Ok(match token {
+    Token::Native { denom } => CosmosMsg::Bank(BankMsg::Send {
+      to_address: recipient.to_string(),
+      amount: vec![Coin::new(amount.u128(), denom)],
+    }),
+    Token::Cw20 { address } => CosmosMsg::Wasm(WasmMsg::Execute {
+      contract_addr: address.to_string(),
+      msg: to_binary(&Cw20ExecuteMsg::Transfer {
+        recipient: recipient.to_string(),
+        amount,
+      })?,
+      funds: vec![],
+    }),
+  })
+}
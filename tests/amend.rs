@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use cw_contract::models::{CREATED, FILLED};
+
+/// Alice and then Carol bid 10 at 1.00, and Bob holds 10 base to sell.
+fn setup() -> (Suite, u64, u64) {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  suite.deposit_quote(CAROL, 100);
+  suite.deposit_base(BOB, 10);
+  let alice_id = suite.submit_ok(ALICE, limit_buy(10, "1"));
+  let carol_id = suite.submit_ok(CAROL, limit_buy(10, "1"));
+  (suite, alice_id, carol_id)
+}
+
+#[test]
+fn reducing_qty_keeps_time_priority() {
+  let (mut suite, alice_id, carol_id) = setup();
+
+  suite.execute(ALICE, &amend(alice_id, Some(5), None)).unwrap();
+  assert_eq!(suite.balances(ALICE).quote_locked, 5);
+
+  suite.submit_ok(BOB, limit_sell(5, "1"));
+
+  assert_eq!(suite.order(ALICE, alice_id).status, FILLED);
+  assert_eq!(suite.order(CAROL, carol_id).status, CREATED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 5,
+      quote_free: 95,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn increasing_qty_replaces_the_order_behind_others() {
+  let (mut suite, alice_id, carol_id) = setup();
+
+  let resp = suite.execute(ALICE, &amend(alice_id, Some(15), None)).unwrap();
+  assert_eq!(attr_value(&resp, "order_id"), alice_id.to_string());
+  assert_eq!(suite.order(ALICE, alice_id).qty_requested.u128(), 15);
+  assert_eq!(suite.balances(ALICE).quote_locked, 15);
+
+  suite.submit_ok(BOB, limit_sell(10, "1"));
+
+  assert_eq!(suite.order(CAROL, carol_id).status, FILLED);
+  assert_eq!(suite.order(ALICE, alice_id).status, CREATED);
+
+  suite.deposit_base(BOB, 15);
+  suite.submit_ok(BOB, limit_sell(15, "1"));
+  assert_eq!(suite.order(ALICE, alice_id).status, FILLED);
+}
+
+#[test]
+fn repricing_replaces_the_order_and_may_match() {
+  let (mut suite, alice_id, _) = setup();
+  suite.submit_ok(BOB, limit_sell(10, "2"));
+
+  suite.execute(ALICE, &amend(alice_id, None, Some("2"))).unwrap();
+
+  assert_eq!(suite.order(ALICE, alice_id).status, FILLED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 10,
+      quote_free: 80,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn amend_rejects_invalid_changes() {
+  let (mut suite, alice_id, _) = setup();
+
+  assert_error(suite.execute(ALICE, &amend(alice_id, Some(0), None)), "InvalidQuantity");
+  assert_error(suite.execute(ALICE, &amend(alice_id, None, Some("0"))), "InvalidPrice");
+  assert_error(suite.execute(BOB, &amend(alice_id, Some(5), None)), "NotAuthorized");
+  assert_error(
+    suite.execute(ALICE, &amend(alice_id, Some(1_000), None)),
+    "InsufficientBalance",
+  );
+  assert_eq!(suite.balances(ALICE).quote_locked, 10);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn resting_buy_locks_its_quote_until_filled() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  suite.submit_ok(ALICE, limit_buy(10, "2"));
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      quote_free: 80,
+      quote_locked: 20,
+      ..Default::default()
+    }
+  );
+
+  suite.deposit_base(BOB, 4);
+  suite.submit_ok(BOB, limit_sell(4, "2"));
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 4,
+      quote_free: 80,
+      quote_locked: 12,
+      ..Default::default()
+    }
+  );
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      quote_free: 8,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn resting_sell_locks_its_base_until_filled() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.submit_ok(ALICE, limit_sell(6, "1"));
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 4,
+      base_locked: 6,
+      ..Default::default()
+    }
+  );
+
+  suite.deposit_quote(BOB, 6);
+  suite.submit_ok(BOB, limit_buy(6, "1"));
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 4,
+      quote_free: 6,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn taker_buy_at_a_better_price_frees_the_difference() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.submit_ok(ALICE, limit_sell(10, "1"));
+  suite.deposit_quote(BOB, 100);
+
+  suite.submit_ok(BOB, limit_buy(10, "2"));
+
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 10,
+      quote_free: 90,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn order_cannot_escrow_more_than_the_free_balance() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  suite.submit_ok(ALICE, limit_buy(60, "1"));
+
+  assert_error(suite.submit(ALICE, limit_buy(41, "1")), "InsufficientBalance");
+  assert_eq!(suite.balances(ALICE).quote_locked, 60);
+}
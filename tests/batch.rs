@@ -0,0 +1,66 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::from_binary;
+use cw_contract::{
+  models::{TimeInForce, CREATED},
+  msg::{BatchOrderResult, ExecuteMsg},
+};
+
+#[test]
+fn non_atomic_batch_rolls_back_a_failed_order() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 100);
+  let ask_id = suite.submit_ok(ALICE, limit_sell(100, "2"));
+  suite.deposit_quote(BOB, 1_000);
+
+  // The FOK buy escrows its balance before failing for lack of liquidity.
+  let resp = suite
+    .execute(
+      BOB,
+      &ExecuteMsg::SubmitBatch {
+        orders: vec![market_buy(500, TimeInForce::Fok), limit_buy(10, "1")],
+        atomic: false,
+      },
+    )
+    .unwrap();
+
+  let results: Vec<BatchOrderResult> = from_binary(resp.data.as_ref().unwrap()).unwrap();
+  assert_eq!(results[0].error.as_deref(), Some("InsufficientLiquidity"));
+  assert!(results[1].order_id.is_some());
+  assert_eq!(attr_value(&resp, "error_count"), "1");
+
+  let ask = suite.order(ALICE, ask_id);
+  assert_eq!(ask.status, CREATED);
+  assert!(ask.qty_matched.is_zero());
+  assert_eq!(suite.balances(ALICE).base_locked, 100);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      quote_free: 990,
+      quote_locked: 10,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn atomic_batch_fails_entirely() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 100);
+  suite.submit_ok(ALICE, limit_sell(100, "2"));
+  suite.deposit_quote(BOB, 1_000);
+
+  assert_error(
+    suite.execute(
+      BOB,
+      &ExecuteMsg::SubmitBatch {
+        orders: vec![limit_buy(10, "1"), market_buy(500, TimeInForce::Fok)],
+        atomic: true,
+      },
+    ),
+    "InsufficientLiquidity",
+  );
+
+  assert_eq!(suite.balances(BOB).quote_free, 1_000);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::Uint64;
+use cw_contract::{
+  models::{OrderSide, TimeInForce, CANCELED, CREATED},
+  msg::ExecuteMsg,
+};
+
+fn cancel_all(
+  limit: Option<u8>,
+  cursor: Option<u64>,
+) -> ExecuteMsg {
+  ExecuteMsg::CancelAll {
+    quote: None,
+    side: None,
+    price_range: None,
+    limit,
+    cursor: cursor.map(Uint64::from),
+  }
+}
+
+#[test]
+fn cancel_all_releases_escrow_of_matching_orders() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 1_000);
+  suite.deposit_base(ALICE, 100);
+  let buy_id = suite.submit_ok(ALICE, limit_buy(10, "1"));
+  let sell_id = suite.submit_ok(ALICE, limit_sell(10, "2"));
+
+  let resp = suite
+    .execute(
+      ALICE,
+      &ExecuteMsg::CancelAll {
+        quote: None,
+        side: Some(OrderSide::Buy),
+        price_range: None,
+        limit: None,
+        cursor: None,
+      },
+    )
+    .unwrap();
+
+  assert_eq!(attr_value(&resp, "order_ids"), buy_id.to_string());
+  assert_eq!(suite.order(ALICE, buy_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, sell_id).status, CREATED);
+  let balances = suite.balances(ALICE);
+  assert_eq!(balances.quote_free, 1_000);
+  assert_eq!(balances.quote_locked, 0);
+  assert_eq!(balances.base_locked, 10);
+}
+
+#[test]
+fn cancel_all_filters_stop_orders_by_trigger_price() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 1_000);
+  let stop_id = suite.submit_ok(ALICE, stop_market(OrderSide::Buy, "12", 100, TimeInForce::Ioc));
+  let buy_id = suite.submit_ok(ALICE, limit_buy(10, "1"));
+
+  let resp = suite
+    .execute(
+      ALICE,
+      &ExecuteMsg::CancelAll {
+        quote: None,
+        side: None,
+        price_range: Some((price("10"), price("15"))),
+        limit: None,
+        cursor: None,
+      },
+    )
+    .unwrap();
+
+  assert_eq!(attr_value(&resp, "order_ids"), stop_id.to_string());
+  assert_eq!(suite.order(ALICE, stop_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, buy_id).status, CREATED);
+  assert_eq!(suite.balances(ALICE).quote_locked, 10);
+}
+
+#[test]
+fn cancel_all_counts_scanned_orders_against_the_limit() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 1_000);
+  let mut order_ids = vec![];
+  for _ in 0..3 {
+    order_ids.push(suite.submit_ok(ALICE, limit_buy(10, "1")));
+  }
+  suite.execute(ALICE, &cancel(order_ids[2])).unwrap();
+
+  // The newest order is already canceled but still counts toward the limit.
+  let resp = suite.execute(ALICE, &cancel_all(Some(2), None)).unwrap();
+  assert_eq!(attr_value(&resp, "canceled_count"), "1");
+  assert_eq!(attr_value(&resp, "order_ids"), order_ids[1].to_string());
+  let cursor: u64 = attr_value(&resp, "cursor").parse().unwrap();
+  assert_eq!(cursor, order_ids[1]);
+  assert_eq!(suite.order(ALICE, order_ids[0]).status, CREATED);
+
+  // Continuing from the cursor cancels the rest and ends the scan.
+  let resp = suite.execute(ALICE, &cancel_all(Some(2), Some(cursor))).unwrap();
+  assert_eq!(attr_value(&resp, "order_ids"), order_ids[0].to_string());
+  assert!(!resp
+    .events
+    .iter()
+    .flat_map(|event| event.attributes.iter())
+    .any(|attr| attr.key == "cursor"));
+  assert_eq!(suite.balances(ALICE).quote_locked, 0);
+}
+
+#[test]
+fn cancel_releases_the_unmatched_escrow() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  let buy_id = suite.submit_ok(ALICE, limit_buy(10, "2"));
+  suite.deposit_base(BOB, 4);
+  suite.submit_ok(BOB, limit_sell(4, "2"));
+
+  let resp = suite.execute(ALICE, &cancel(buy_id)).unwrap();
+
+  assert_eq!(attr_value(&resp, "qty_canceled"), "6");
+  assert_eq!(attr_value(&resp, "refund"), "12");
+  assert_eq!(suite.order(ALICE, buy_id).status, CANCELED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 4,
+      quote_free: 92,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn canceled_order_leaves_the_book() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  let sell_id = suite.submit_ok(ALICE, limit_sell(10, "1"));
+  suite.execute(ALICE, &cancel(sell_id)).unwrap();
+  suite.deposit_quote(BOB, 10);
+
+  let buy_id = suite.submit_ok(BOB, limit_buy(10, "1"));
+
+  assert_eq!(suite.order(BOB, buy_id).status, CREATED);
+  assert_eq!(suite.balances(ALICE).base_free, 10);
+  assert_eq!(suite.balances(BOB).quote_locked, 10);
+}
+
+#[test]
+fn cancel_requires_an_open_order_of_the_sender() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  let buy_id = suite.submit_ok(ALICE, limit_buy(10, "1"));
+
+  assert_error(suite.execute(BOB, &cancel(buy_id)), "NotAuthorized");
+  assert_error(suite.execute(ALICE, &cancel(buy_id + 1)), "OrderNotFound");
+  suite.execute(ALICE, &cancel(buy_id)).unwrap();
+  assert_error(suite.execute(ALICE, &cancel(buy_id)), "OrderNotOpen");
+  assert_eq!(suite.balances(ALICE).quote_free, 100);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::{Addr, Uint128};
+use cw_contract::{
+  models::{Order, CANCELED, CREATED},
+  msg::{ExecuteMsg, OrderRequest, QueryMsg},
+};
+
+fn limit_buy_as(
+  qty: u128,
+  price_str: &str,
+  client_order_id: &str,
+) -> OrderRequest {
+  limit_buy_with(
+    qty,
+    price_str,
+    LimitOpts {
+      client_order_id: Some(client_order_id.to_owned()),
+      ..Default::default()
+    },
+  )
+}
+
+fn order_by_client_id(
+  suite: &Suite,
+  account: &str,
+  client_order_id: &str,
+) -> anyhow::Result<Order> {
+  Ok(suite.app.wrap().query_wasm_smart(
+    &suite.contract,
+    &QueryMsg::Order {
+      account: Addr::unchecked(account),
+      order_id: None,
+      client_order_id: Some(client_order_id.to_owned()),
+    },
+  )?)
+}
+
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 100);
+  suite.deposit_quote(BOB, 100);
+  suite
+}
+
+#[test]
+fn order_can_be_looked_up_by_client_order_id() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, limit_buy_as(10, "1", "a-1"));
+
+  let order = order_by_client_id(&suite, ALICE, "a-1").unwrap();
+  assert_eq!(order.id.unwrap().u64(), order_id);
+  assert_eq!(order.client_order_id.as_deref(), Some("a-1"));
+  // Client order IDs are scoped to their owner.
+  assert!(order_by_client_id(&suite, BOB, "a-1").is_err());
+}
+
+#[test]
+fn client_order_id_may_only_be_used_once_per_owner() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, limit_buy_as(10, "1", "a-1"));
+  suite.submit_ok(BOB, limit_buy_as(10, "1", "a-1"));
+
+  assert_error(
+    suite.submit(ALICE, limit_buy_as(10, "1", "a-1")),
+    "DuplicateClientOrderId",
+  );
+  suite.execute(ALICE, &cancel(order_id)).unwrap();
+  assert_error(
+    suite.submit(ALICE, limit_buy_as(10, "1", "a-1")),
+    "DuplicateClientOrderId",
+  );
+  assert_eq!(suite.balances(ALICE).quote_free, 100);
+}
+
+#[test]
+fn client_order_id_must_be_nonempty_and_short() {
+  let mut suite = setup();
+  assert_error(suite.submit(ALICE, limit_buy_as(10, "1", "")), "InvalidClientOrderId");
+  assert_error(
+    suite.submit(ALICE, limit_buy_as(10, "1", &"x".repeat(65))),
+    "InvalidClientOrderId",
+  );
+}
+
+#[test]
+fn cancel_by_client_order_id() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, limit_buy_as(10, "1", "a-1"));
+
+  suite
+    .execute(
+      ALICE,
+      &ExecuteMsg::Cancel {
+        order_id: None,
+        client_order_id: Some("a-1".to_owned()),
+      },
+    )
+    .unwrap();
+
+  assert_eq!(suite.order(ALICE, order_id).status, CANCELED);
+  assert_error(
+    suite.execute(
+      ALICE,
+      &ExecuteMsg::Cancel {
+        order_id: Some(order_id.into()),
+        client_order_id: Some("a-1".to_owned()),
+      },
+    ),
+    "InvalidOrderId",
+  );
+}
+
+#[test]
+fn replaced_order_keeps_its_client_order_id() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, limit_buy_as(10, "1", "a-1"));
+
+  suite
+    .execute(
+      ALICE,
+      &ExecuteMsg::Amend {
+        order_id: None,
+        client_order_id: Some("a-1".to_owned()),
+        new_qty: Some(Uint128::from(20u128)),
+        new_price: None,
+      },
+    )
+    .unwrap();
+
+  let order = order_by_client_id(&suite, ALICE, "a-1").unwrap();
+  assert_eq!(order.id.unwrap().u64(), order_id);
+  assert_eq!(order.client_order_id.as_deref(), Some("a-1"));
+  assert_eq!(order.qty_requested.u128(), 20);
+  assert_eq!(order.status, CREATED);
+}
@@ -0,0 +1,590 @@
+#![allow(dead_code)]
+
+use std::str::FromStr;
+
+use cosmwasm_std::{coins, from_slice, to_binary, Addr, Decimal256, Uint128, Uint64};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_contract::{
+  contract::{execute, instantiate, query, reply},
+  models::{Order, OrderSide, PostOnly, SelfTradePrevention, TimeInForce, TrailingOffset},
+  msg::{
+    BaseTokenInitArgs, DepthResponse, DustResponse, ExecuteMsg, InstantiateMsg, OrderRequest, QueryMsg,
+    QuoteTokenInitArgs, ReceiveMsg, SelectResponse,
+  },
+};
+use cw_lib::models::Token;
+use cw_multi_test::{App, AppBuilder, AppResponse, ContractWrapper, Executor};
+
+pub const ADMIN: &str = "admin";
+pub const ALICE: &str = "alice";
+pub const BOB: &str = "bob";
+pub const CAROL: &str = "carol";
+pub const QUOTE_DENOM: &str = "uusd";
+pub const BASE_CAP: u128 = 1_000_000_000_000;
+pub const QUOTE_SUPPLY: u128 = 1_000_000_000_000;
+
+/// Optional fields of a limit order request, left at their defaults unless
+/// set.
+#[derive(Default)]
+pub struct LimitOpts {
+  pub tif: Option<TimeInForce>,
+  pub post_only: Option<PostOnly>,
+  pub display_qty: Option<u128>,
+  pub min_fill_qty: Option<u128>,
+  pub all_or_none: Option<bool>,
+  pub self_trade_prevention: Option<SelfTradePrevention>,
+  pub client_order_id: Option<String>,
+}
+
+/// Optional slippage limits of a market order request, left unset unless set.
+#[derive(Default)]
+pub struct MarketOpts {
+  /// Max price for buys, or min price for sells.
+  pub price_limit: Option<&'static str>,
+  pub min_receive: Option<u128>,
+}
+
+/// Free and locked internal balances of an account.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Balances {
+  pub base_free: u128,
+  pub base_locked: u128,
+  pub quote_free: u128,
+  pub quote_locked: u128,
+}
+
+pub struct Suite {
+  pub app: App,
+  pub contract: Addr,
+  pub base_token: Addr,
+}
+
+impl Suite {
+  /// Instantiate the order book with one native quote token.
+  pub fn new() -> Self {
+    Self::with_msg(|_| {})
+  }
+
+  /// Instantiate the order book after customizing the default instantiate msg.
+  pub fn with_msg(customize: impl FnOnce(&mut InstantiateMsg)) -> Self {
+    let mut app = AppBuilder::new().build(|router, _, storage| {
+      for account in [ALICE, BOB, CAROL] {
+        router
+          .bank
+          .init_balance(storage, &Addr::unchecked(account), coins(QUOTE_SUPPLY, QUOTE_DENOM))
+          .unwrap();
+      }
+    });
+
+    let cw20_code_id = app.store_code(Box::new(ContractWrapper::new(
+      cw20_base::contract::execute,
+      cw20_base::contract::instantiate,
+      cw20_base::contract::query,
+    )));
+    let code_id = app.store_code(Box::new(
+      ContractWrapper::new(execute, instantiate, query).with_reply(reply),
+    ));
+
+    let mut msg = InstantiateMsg {
+      base_token: BaseTokenInitArgs {
+        code_id: Uint64::from(cw20_code_id),
+        name: "Base".to_owned(),
+        symbol: "BASE".to_owned(),
+        decimals: 6,
+        marketing: None,
+        cap: Uint128::from(BASE_CAP),
+      },
+      quote_tokens: vec![QuoteTokenInitArgs {
+        token: quote(),
+        price_precision: 2,
+        tick_size: None,
+        lot_size: None,
+        min_qty: None,
+        min_notional: None,
+      }],
+      dust_policy: None,
+    };
+    customize(&mut msg);
+
+    let contract = app
+      .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "order book", None)
+      .unwrap();
+    let base_token = match query_base_token(&app, &contract) {
+      Token::Cw20 { address } => address,
+      Token::Native { .. } => panic!("base token is not a CW20"),
+    };
+
+    Self {
+      app,
+      contract,
+      base_token,
+    }
+  }
+
+  pub fn execute(
+    &mut self,
+    sender: &str,
+    msg: &ExecuteMsg,
+  ) -> anyhow::Result<AppResponse> {
+    self
+      .app
+      .execute_contract(Addr::unchecked(sender), self.contract.clone(), msg, &[])
+  }
+
+  /// Submit an order, escrowing its funds from the sender's internal balances.
+  pub fn submit(
+    &mut self,
+    sender: &str,
+    req: OrderRequest,
+  ) -> anyhow::Result<AppResponse> {
+    self.execute(sender, &ExecuteMsg::Submit(req))
+  }
+
+  /// Submit an order with native quote token attached as its funds.
+  pub fn submit_with_funds(
+    &mut self,
+    sender: &str,
+    req: OrderRequest,
+    amount: u128,
+  ) -> anyhow::Result<AppResponse> {
+    self.app.execute_contract(
+      Addr::unchecked(sender),
+      self.contract.clone(),
+      &ExecuteMsg::Submit(req),
+      &coins(amount, QUOTE_DENOM),
+    )
+  }
+
+  /// Submit an order, returning the ID of the new order.
+  pub fn submit_ok(
+    &mut self,
+    sender: &str,
+    req: OrderRequest,
+  ) -> u64 {
+    order_id(&self.submit(sender, req).unwrap())
+  }
+
+  /// Deposit native quote token into the sender's internal balance.
+  pub fn deposit_quote(
+    &mut self,
+    sender: &str,
+    amount: u128,
+  ) {
+    self
+      .app
+      .execute_contract(
+        Addr::unchecked(sender),
+        self.contract.clone(),
+        &ExecuteMsg::Deposit {},
+        &coins(amount, QUOTE_DENOM),
+      )
+      .unwrap();
+  }
+
+  /// Transfer base token to the account and deposit it into its internal
+  /// balance.
+  pub fn deposit_base(
+    &mut self,
+    account: &str,
+    amount: u128,
+  ) {
+    self.transfer_base(account, amount);
+    self.send_base(account, amount, &ReceiveMsg::Deposit {}).unwrap();
+  }
+
+  /// Transfer base token from the admin, who holds its whole supply.
+  pub fn transfer_base(
+    &mut self,
+    recipient: &str,
+    amount: u128,
+  ) {
+    self
+      .app
+      .execute_contract(
+        Addr::unchecked(ADMIN),
+        self.base_token.clone(),
+        &Cw20ExecuteMsg::Transfer {
+          recipient: recipient.to_owned(),
+          amount: Uint128::from(amount),
+        },
+        &[],
+      )
+      .unwrap();
+  }
+
+  /// Send base token to the order book with the given receive msg.
+  pub fn send_base<T: serde::Serialize>(
+    &mut self,
+    sender: &str,
+    amount: u128,
+    msg: &T,
+  ) -> anyhow::Result<AppResponse> {
+    self.app.execute_contract(
+      Addr::unchecked(sender),
+      self.base_token.clone(),
+      &Cw20ExecuteMsg::Send {
+        contract: self.contract.to_string(),
+        amount: Uint128::from(amount),
+        msg: to_binary(msg).unwrap(),
+      },
+      &[],
+    )
+  }
+
+  pub fn balances(
+    &self,
+    account: &str,
+  ) -> Balances {
+    let resp: SelectResponse = self
+      .app
+      .wrap()
+      .query_wasm_smart(
+        &self.contract,
+        &QueryMsg::Select {
+          fields: None,
+          account: Some(Addr::unchecked(account)),
+        },
+      )
+      .unwrap();
+    let view = resp.account.unwrap();
+    let quote = view.quote_balances.iter().find(|balance| balance.token == quote());
+    Balances {
+      base_free: view.base_balance.free.u128(),
+      base_locked: view.base_balance.locked.u128(),
+      quote_free: quote.map_or(0, |balance| balance.free.u128()),
+      quote_locked: quote.map_or(0, |balance| balance.locked.u128()),
+    }
+  }
+
+  /// Total quote token held by the order book contract itself.
+  pub fn contract_quote_balance(&self) -> u128 {
+    self
+      .app
+      .wrap()
+      .query_balance(&self.contract, QUOTE_DENOM)
+      .unwrap()
+      .amount
+      .u128()
+  }
+
+  /// Native quote token held by an address.
+  pub fn quote_balance(
+    &self,
+    address: &str,
+  ) -> u128 {
+    self
+      .app
+      .wrap()
+      .query_balance(address, QUOTE_DENOM)
+      .unwrap()
+      .amount
+      .u128()
+  }
+
+  /// Base token held by an address in the CW20 contract.
+  pub fn base_balance(
+    &self,
+    address: &str,
+  ) -> u128 {
+    let resp: cw20::BalanceResponse = self
+      .app
+      .wrap()
+      .query_wasm_smart(
+        &self.base_token,
+        &Cw20QueryMsg::Balance {
+          address: address.to_owned(),
+        },
+      )
+      .unwrap();
+    resp.balance.u128()
+  }
+
+  /// Look up one of the account's orders by its ID.
+  pub fn order(
+    &self,
+    account: &str,
+    order_id: u64,
+  ) -> Order {
+    self
+      .app
+      .wrap()
+      .query_wasm_smart(
+        &self.contract,
+        &QueryMsg::Order {
+          account: Addr::unchecked(account),
+          order_id: Some(order_id.into()),
+          client_order_id: None,
+        },
+      )
+      .unwrap()
+  }
+
+  pub fn depth(&self) -> DepthResponse {
+    self
+      .app
+      .wrap()
+      .query_wasm_smart(
+        &self.contract,
+        &QueryMsg::Depth {
+          quote: quote(),
+          limit: None,
+        },
+      )
+      .unwrap()
+  }
+
+  pub fn dust(&self) -> u128 {
+    let resp: DustResponse = self
+      .app
+      .wrap()
+      .query_wasm_smart(&self.contract, &QueryMsg::Dust {})
+      .unwrap();
+    resp.dust.iter().map(|dust| dust.amount.u128()).sum()
+  }
+
+  pub fn next_block(&mut self) {
+    self.app.update_block(|block| {
+      block.height += 1;
+      block.time = block.time.plus_seconds(5);
+    });
+  }
+}
+
+impl Default for Suite {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+pub fn quote() -> Token {
+  Token::Native {
+    denom: QUOTE_DENOM.to_owned(),
+  }
+}
+
+pub fn price(value: &str) -> Decimal256 {
+  Decimal256::from_str(value).unwrap()
+}
+
+/// Load the base token saved by the order book once it instantiated the CW20.
+fn query_base_token(
+  app: &App,
+  contract: &Addr,
+) -> Token {
+  let data = app.wrap().query_wasm_raw(contract, b"base_token".as_slice()).unwrap();
+  from_slice(&data.expect("base token not saved")).unwrap()
+}
+
+/// Get the order ID of a submitted order from its response attributes.
+pub fn order_id(resp: &AppResponse) -> u64 {
+  attr_value(resp, "order_id").parse().unwrap()
+}
+
+/// Get the first value of a wasm event attribute in a response.
+pub fn attr_value(
+  resp: &AppResponse,
+  key: &str,
+) -> String {
+  resp
+    .events
+    .iter()
+    .filter(|event| event.ty == "wasm")
+    .flat_map(|event| event.attributes.iter())
+    .find(|attr| attr.key == key)
+    .map(|attr| attr.value.clone())
+    .unwrap_or_else(|| panic!("missing attribute {}", key))
+}
+
+/// Get the values of an attribute in every custom wasm event of a type.
+pub fn event_attr_values(
+  resp: &AppResponse,
+  ty: &str,
+  key: &str,
+) -> Vec<String> {
+  let wasm_ty = format!("wasm-{}", ty);
+  resp
+    .events
+    .iter()
+    .filter(|event| event.ty == wasm_ty)
+    .flat_map(|event| event.attributes.iter())
+    .filter(|attr| attr.key == key)
+    .map(|attr| attr.value.clone())
+    .collect()
+}
+
+pub fn cancel(order_id: u64) -> ExecuteMsg {
+  ExecuteMsg::Cancel {
+    order_id: Some(order_id.into()),
+    client_order_id: None,
+  }
+}
+
+/// Assert that a result failed with the given contract error.
+pub fn amend(
+  order_id: u64,
+  new_qty: Option<u128>,
+  new_price: Option<&str>,
+) -> ExecuteMsg {
+  ExecuteMsg::Amend {
+    order_id: Some(order_id.into()),
+    client_order_id: None,
+    new_qty: new_qty.map(Uint128::from),
+    new_price: new_price.map(price),
+  }
+}
+
+pub fn assert_error<T: std::fmt::Debug>(
+  result: anyhow::Result<T>,
+  expected: &str,
+) {
+  let err = result.unwrap_err();
+  assert_eq!(err.root_cause().to_string(), expected);
+}
+
+pub fn limit_buy(
+  qty: u128,
+  price_str: &str,
+) -> OrderRequest {
+  limit_buy_with(qty, price_str, LimitOpts::default())
+}
+
+pub fn limit_buy_with(
+  qty: u128,
+  price_str: &str,
+  opts: LimitOpts,
+) -> OrderRequest {
+  OrderRequest::LimitBuy {
+    quote: quote(),
+    qty: Uint128::from(qty),
+    price: price(price_str),
+    tif: opts.tif.unwrap_or(TimeInForce::Gtc),
+    post_only: opts.post_only,
+    display_qty: opts.display_qty.map(Uint128::from),
+    min_fill_qty: opts.min_fill_qty.map(Uint128::from),
+    all_or_none: opts.all_or_none,
+    self_trade_prevention: opts.self_trade_prevention,
+    client_order_id: opts.client_order_id,
+  }
+}
+
+pub fn limit_sell(
+  qty: u128,
+  price_str: &str,
+) -> OrderRequest {
+  limit_sell_with(qty, price_str, LimitOpts::default())
+}
+
+pub fn limit_sell_with(
+  qty: u128,
+  price_str: &str,
+  opts: LimitOpts,
+) -> OrderRequest {
+  OrderRequest::LimitSell {
+    quote: quote(),
+    qty: Uint128::from(qty),
+    price: price(price_str),
+    tif: opts.tif.unwrap_or(TimeInForce::Gtc),
+    post_only: opts.post_only,
+    display_qty: opts.display_qty.map(Uint128::from),
+    min_fill_qty: opts.min_fill_qty.map(Uint128::from),
+    all_or_none: opts.all_or_none,
+    self_trade_prevention: opts.self_trade_prevention,
+    client_order_id: opts.client_order_id,
+  }
+}
+
+pub fn market_buy(
+  balance: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  market_buy_with(balance, tif, MarketOpts::default())
+}
+
+pub fn market_buy_with(
+  balance: u128,
+  tif: TimeInForce,
+  opts: MarketOpts,
+) -> OrderRequest {
+  OrderRequest::MarketBuy {
+    quote: quote(),
+    balance: Uint128::from(balance),
+    tif,
+    max_price: opts.price_limit.map(price),
+    min_receive: opts.min_receive.map(Uint128::from),
+    self_trade_prevention: None,
+    client_order_id: None,
+  }
+}
+
+pub fn market_sell(
+  qty: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  market_sell_with(qty, tif, MarketOpts::default())
+}
+
+pub fn market_sell_with(
+  qty: u128,
+  tif: TimeInForce,
+  opts: MarketOpts,
+) -> OrderRequest {
+  OrderRequest::MarketSell {
+    quote: quote(),
+    qty: Uint128::from(qty),
+    tif,
+    min_price: opts.price_limit.map(price),
+    min_receive: opts.min_receive.map(Uint128::from),
+    self_trade_prevention: None,
+    client_order_id: None,
+  }
+}
+
+pub fn stop_market(
+  side: OrderSide,
+  trigger_price: &str,
+  amount: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  OrderRequest::StopMarket {
+    quote: quote(),
+    side,
+    trigger_price: price(trigger_price),
+    amount: Uint128::from(amount),
+    tif,
+    client_order_id: None,
+  }
+}
+
+pub fn stop_limit(
+  side: OrderSide,
+  trigger_price: &str,
+  qty: u128,
+  price_str: &str,
+  tif: TimeInForce,
+) -> OrderRequest {
+  OrderRequest::StopLimit {
+    quote: quote(),
+    side,
+    trigger_price: price(trigger_price),
+    qty: Uint128::from(qty),
+    price: price(price_str),
+    tif,
+    client_order_id: None,
+  }
+}
+
+pub fn trailing_stop(
+  side: OrderSide,
+  offset: TrailingOffset,
+  amount: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  OrderRequest::TrailingStop {
+    quote: quote(),
+    side,
+    offset,
+    amount: Uint128::from(amount),
+    tif,
+    client_order_id: None,
+  }
+}
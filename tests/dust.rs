@@ -0,0 +1,142 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::{Addr, Uint128};
+use cw_contract::{
+  models::{DustPolicy, TimeInForce, FILLED, MATCHED},
+  msg::ExecuteMsg,
+};
+
+/// Instantiate a market with an ask of 10 at 10.
+fn setup(dust_policy: Option<DustPolicy>) -> Suite {
+  let mut suite = Suite::with_msg(|msg| msg.dust_policy = dust_policy);
+  suite.deposit_base(ALICE, 10);
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+  suite.deposit_quote(BOB, 1_000);
+  suite
+}
+
+fn collect_by_carol() -> Option<DustPolicy> {
+  Some(DustPolicy::Collect {
+    recipient: Addr::unchecked(CAROL),
+  })
+}
+
+#[test]
+fn sub_unit_remainder_is_refunded_by_default() {
+  let mut suite = setup(None);
+  let order_id = suite.submit_ok(BOB, market_buy(105, TimeInForce::Ioc));
+
+  assert_eq!(suite.order(BOB, order_id).status, MATCHED);
+  assert_eq!(suite.balances(BOB).base_free, 10);
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 100);
+  assert_eq!(suite.dust(), 0);
+}
+
+#[test]
+fn fok_buy_is_filled_when_only_dust_remains() {
+  let mut suite = setup(None);
+  let order_id = suite.submit_ok(BOB, market_buy(105, TimeInForce::Fok));
+
+  assert_eq!(suite.order(BOB, order_id).status, FILLED);
+  assert_eq!(suite.balances(BOB).base_free, 10);
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 100);
+}
+
+#[test]
+fn fok_buy_that_cannot_buy_a_unit_fails() {
+  let mut suite = setup(collect_by_carol());
+  assert_error(
+    suite.submit(BOB, market_buy(5, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+
+  assert_eq!(suite.balances(BOB).quote_free, 1_000);
+  assert_eq!(suite.dust(), 0);
+}
+
+#[test]
+fn fok_buy_against_empty_book_fails() {
+  let mut suite = setup(None);
+  suite.submit_ok(BOB, market_buy(100, TimeInForce::Fok));
+  assert_error(
+    suite.submit(BOB, market_buy(100, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 100);
+}
+
+#[test]
+fn remainder_that_can_buy_a_unit_is_not_dust() {
+  let mut suite = setup(collect_by_carol());
+  // The book runs out with enough left to buy another unit at 10.
+  assert_error(
+    suite.submit(BOB, market_buy(150, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+  suite.submit_ok(BOB, market_buy(150, TimeInForce::Ioc));
+
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 100);
+  assert_eq!(suite.dust(), 0);
+}
+
+#[test]
+fn collected_dust_is_credited_to_recipient() {
+  let mut suite = setup(collect_by_carol());
+  suite.submit_ok(BOB, market_buy(105, TimeInForce::Ioc));
+
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 105);
+  assert_eq!(suite.balances(CAROL).quote_free, 5);
+  assert_eq!(suite.dust(), 5);
+}
+
+#[test]
+fn sub_lot_remainder_is_dust() {
+  let mut suite = Suite::with_msg(|msg| {
+    msg.quote_tokens[0].lot_size = Some(Uint128::new(10));
+    msg.dust_policy = collect_by_carol();
+  });
+  suite.deposit_base(ALICE, 100);
+  suite.submit_ok(ALICE, limit_sell(100, "1"));
+  suite.deposit_quote(BOB, 1_000);
+  // 15 would buy 15 at 1.00, but only a lot of 10 is bought.
+  suite.submit_ok(BOB, market_buy(15, TimeInForce::Ioc));
+
+  assert_eq!(suite.balances(BOB).base_free, 10);
+  assert_eq!(suite.balances(BOB).quote_free, 1_000 - 15);
+  assert_eq!(suite.balances(CAROL).quote_free, 5);
+  assert_eq!(suite.dust(), 5);
+}
+
+#[test]
+fn funds_taken_in_equal_funds_paid_out() {
+  let mut suite = setup(collect_by_carol());
+  suite.submit_ok(BOB, market_buy(105, TimeInForce::Ioc));
+
+  for account in [ALICE, BOB, CAROL] {
+    suite
+      .execute(account, &ExecuteMsg::WithdrawAll { recipient: None })
+      .unwrap();
+  }
+
+  // Everything deposited has been withdrawn, and no quote token was created
+  // or destroyed along the way.
+  assert_eq!(suite.contract_quote_balance(), 0);
+  let total_quote: u128 = [ALICE, BOB, CAROL]
+    .iter()
+    .map(|account| {
+      suite
+        .app
+        .wrap()
+        .query_balance(*account, QUOTE_DENOM)
+        .unwrap()
+        .amount
+        .u128()
+    })
+    .sum();
+  assert_eq!(total_quote, 3 * QUOTE_SUPPLY);
+  assert_eq!(suite.balances(CAROL).quote_free, 0);
+  assert_eq!(suite.base_balance(BOB), 10);
+  assert_eq!(suite.base_balance(suite.contract.as_str()), 0);
+}
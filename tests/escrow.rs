@@ -0,0 +1,62 @@
+mod common;
+
+use common::*;
+use cw_contract::models::{TimeInForce, CREATED};
+
+#[test]
+fn limit_buy_escrows_attached_coins() {
+  let mut suite = Suite::new();
+  let resp = suite.submit_with_funds(ALICE, limit_buy(10, "2"), 20).unwrap();
+
+  assert_eq!(suite.order(ALICE, order_id(&resp)).status, CREATED);
+  assert_eq!(suite.balances(ALICE).quote_locked, 20);
+  assert_eq!(suite.contract_quote_balance(), 20);
+  assert_eq!(suite.quote_balance(ALICE), QUOTE_SUPPLY - 20);
+}
+
+#[test]
+fn limit_buy_rejects_under_and_over_payment() {
+  let mut suite = Suite::new();
+  assert_error(
+    suite.submit_with_funds(ALICE, limit_buy(10, "2"), 19),
+    "InsufficientFunds",
+  );
+  assert_error(suite.submit_with_funds(ALICE, limit_buy(10, "2"), 21), "ExcessFunds");
+  assert_error(suite.submit(ALICE, limit_buy(10, "2")), "InsufficientBalance");
+  assert_eq!(suite.contract_quote_balance(), 0);
+}
+
+#[test]
+fn limit_buy_whose_quote_amount_overflows_fails() {
+  let mut suite = Suite::new();
+  assert_error(
+    suite.submit_with_funds(ALICE, limit_buy(u128::MAX / 10, "1000"), 1),
+    "QuoteAmountOverflow",
+  );
+}
+
+#[test]
+fn unfilled_ioc_limit_buy_refunds_its_unmatched_cost() {
+  let mut suite = Suite::new();
+  suite.transfer_base(BOB, 4);
+  suite.send_base(BOB, 4, &limit_sell(4, "2")).unwrap();
+
+  let resp = suite
+    .submit_with_funds(
+      ALICE,
+      limit_buy_with(
+        10,
+        "2",
+        LimitOpts {
+          tif: Some(TimeInForce::Ioc),
+          ..Default::default()
+        },
+      ),
+      20,
+    )
+    .unwrap();
+
+  assert_eq!(suite.order(ALICE, order_id(&resp)).qty_matched.u128(), 4);
+  assert_eq!(suite.quote_balance(ALICE), QUOTE_SUPPLY - 8);
+  assert_eq!(suite.contract_quote_balance(), 8);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{TimeInForce, CREATED, EXPIRED},
+  msg::OrderRequest,
+};
+
+fn limit_sell_until(
+  qty: u128,
+  price_str: &str,
+  tif: TimeInForce,
+) -> OrderRequest {
+  limit_sell_with(
+    qty,
+    price_str,
+    LimitOpts {
+      tif: Some(tif),
+      ..Default::default()
+    },
+  )
+}
+
+/// Alice rests an ask that expires after the current block, and Bob holds
+/// quote to buy it.
+fn setup(tif: impl FnOnce(&Suite) -> TimeInForce) -> (Suite, u64) {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_quote(BOB, 10);
+  let tif = tif(&suite);
+  let ask_id = suite.submit_ok(ALICE, limit_sell_until(10, "1", tif));
+  (suite, ask_id)
+}
+
+fn gtd_next_block(suite: &Suite) -> TimeInForce {
+  TimeInForce::Gtd {
+    expires_at: suite.app.block_info().time.plus_seconds(5),
+  }
+}
+
+fn gtb_next_block(suite: &Suite) -> TimeInForce {
+  TimeInForce::Gtb {
+    height: suite.app.block_info().height + 1,
+  }
+}
+
+#[test]
+fn unexpired_order_matches() {
+  for tif in [gtd_next_block, gtb_next_block] {
+    let (mut suite, ask_id) = setup(tif);
+    assert_eq!(suite.order(ALICE, ask_id).status, CREATED);
+
+    suite.submit_ok(BOB, limit_buy(10, "1"));
+
+    assert_eq!(suite.balances(ALICE).quote_free, 10);
+  }
+}
+
+#[test]
+fn expired_order_is_skipped_and_refunded() {
+  for tif in [gtd_next_block, gtb_next_block] {
+    let (mut suite, ask_id) = setup(tif);
+    suite.next_block();
+
+    // Queries report the expiry before anything removes the order.
+    assert_eq!(suite.order(ALICE, ask_id).status, EXPIRED);
+    assert_eq!(suite.balances(ALICE).base_locked, 10);
+
+    let bid_id = suite.submit_ok(BOB, limit_buy(10, "1"));
+
+    assert_eq!(suite.order(BOB, bid_id).status, CREATED);
+    assert_eq!(
+      suite.balances(ALICE),
+      Balances {
+        base_free: 10,
+        ..Default::default()
+      }
+    );
+  }
+}
+
+#[test]
+fn order_that_is_already_expired_is_rejected() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  let block = suite.app.block_info();
+
+  for tif in [
+    TimeInForce::Gtd { expires_at: block.time },
+    TimeInForce::Gtb { height: block.height },
+  ] {
+    assert_error(suite.submit(ALICE, limit_sell_until(10, "1", tif)), "InvalidExpiration");
+  }
+  assert_eq!(suite.balances(ALICE).base_free, 10);
+}
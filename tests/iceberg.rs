@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{TimeInForce, FILLED, PARTIAL},
+  msg::OrderRequest,
+};
+
+fn iceberg_sell(
+  qty: u128,
+  display_qty: u128,
+  price_str: &str,
+  tif: TimeInForce,
+) -> OrderRequest {
+  limit_sell_with(
+    qty,
+    price_str,
+    LimitOpts {
+      tif: Some(tif),
+      display_qty: Some(display_qty),
+      ..Default::default()
+    },
+  )
+}
+
+/// Alice rests an iceberg ask of 30 showing 10 at a time, and then Carol an
+/// ask of 10, both at 1.
+fn setup() -> (Suite, u64, u64) {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 30);
+  suite.deposit_base(CAROL, 10);
+  let iceberg_id = suite.submit_ok(ALICE, iceberg_sell(30, 10, "1", TimeInForce::Gtc));
+  let carol_id = suite.submit_ok(CAROL, limit_sell(10, "1"));
+  suite.deposit_quote(BOB, 100);
+  (suite, iceberg_id, carol_id)
+}
+
+#[test]
+fn book_shows_only_the_displayed_slice() {
+  let (suite, _, _) = setup();
+
+  let depth = suite.depth();
+  assert_eq!(depth.asks.len(), 1);
+  assert_eq!(depth.asks[0].qty.u128(), 20);
+  // The whole order is escrowed, including its hidden reserve.
+  assert_eq!(suite.balances(ALICE).base_locked, 30);
+}
+
+#[test]
+fn next_slice_queues_behind_orders_at_its_price() {
+  let (mut suite, iceberg_id, carol_id) = setup();
+
+  suite.submit_ok(BOB, limit_buy(15, "1"));
+
+  assert_eq!(suite.order(ALICE, iceberg_id).qty_matched.u128(), 10);
+  assert_eq!(suite.order(CAROL, carol_id).qty_matched.u128(), 5);
+  assert_eq!(suite.depth().asks[0].qty.u128(), 15);
+}
+
+#[test]
+fn reserve_matches_once_its_price_is_exhausted() {
+  let (mut suite, iceberg_id, carol_id) = setup();
+
+  suite.submit_ok(BOB, limit_buy(35, "1"));
+
+  let iceberg = suite.order(ALICE, iceberg_id);
+  assert_eq!(iceberg.status, PARTIAL);
+  assert_eq!(iceberg.qty_matched.u128(), 25);
+  assert_eq!(suite.order(CAROL, carol_id).status, FILLED);
+  assert_eq!(suite.depth().asks[0].qty.u128(), 5);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_locked: 5,
+      quote_free: 25,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn iceberg_must_rest_with_a_nonzero_slice() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 30);
+
+  assert_error(
+    suite.submit(ALICE, iceberg_sell(30, 0, "1", TimeInForce::Gtc)),
+    "InvalidQuantity",
+  );
+  assert_error(
+    suite.submit(ALICE, iceberg_sell(30, 10, "1", TimeInForce::Ioc)),
+    "TimeInForceNotAllowed",
+  );
+}
+
+#[test]
+fn iceberg_fills_at_a_fractional_price_conserve_quote() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 30);
+  suite.submit_ok(ALICE, iceberg_sell(30, 10, "0.15", TimeInForce::Gtc));
+  suite.deposit_quote(BOB, 100);
+  // Each slice of 10 at 0.15 costs 1.5, rounded down to 1.
+  suite.submit_ok(BOB, limit_buy(30, "0.15"));
+
+  let (alice, bob) = (suite.balances(ALICE), suite.balances(BOB));
+  assert_eq!(alice.quote_free, 3);
+  assert_eq!(bob.base_free, 30);
+  assert_eq!(bob.quote_free, 97);
+  let quote_held = alice.quote_free + alice.quote_locked + bob.quote_free + bob.quote_locked;
+  assert_eq!(suite.contract_quote_balance(), quote_held);
+}
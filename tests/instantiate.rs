@@ -0,0 +1,11 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn instantiate_saves_the_new_base_token() {
+  let suite = Suite::new();
+
+  assert_eq!(suite.base_balance(ADMIN), BASE_CAP);
+  assert_eq!(suite.balances(ADMIN), Balances::default());
+}
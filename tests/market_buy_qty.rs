@@ -0,0 +1,101 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::Uint128;
+use cw_contract::{
+  models::{TimeInForce, FILLED, MATCHED},
+  msg::OrderRequest,
+};
+
+fn market_buy_qty(
+  qty: u128,
+  max_spend: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  OrderRequest::MarketBuyQty {
+    quote: quote(),
+    qty: Uint128::from(qty),
+    max_spend: Uint128::from(max_spend),
+    tif,
+    max_price: None,
+    min_receive: None,
+    self_trade_prevention: None,
+    client_order_id: None,
+  }
+}
+
+/// Alice rests asks of 10 at 1 and 3, and Bob holds 100 quote.
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 20);
+  suite.submit_ok(ALICE, limit_sell(10, "1"));
+  suite.submit_ok(ALICE, limit_sell(10, "3"));
+  suite.deposit_quote(BOB, 100);
+  suite
+}
+
+#[test]
+fn buys_exactly_the_qty_and_refunds_the_rest() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(BOB, market_buy_qty(15, 100, TimeInForce::Ioc));
+
+  let order = suite.order(BOB, order_id);
+  assert_eq!(order.status, FILLED);
+  assert_eq!(order.qty_matched.u128(), 15);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 15,
+      quote_free: 75,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn ioc_buy_stops_at_its_max_spend() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(BOB, market_buy_qty(15, 20, TimeInForce::Ioc));
+
+  let order = suite.order(BOB, order_id);
+  assert_eq!(order.status, MATCHED);
+  assert_eq!(order.qty_matched.u128(), 13);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 13,
+      quote_free: 81,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn fok_buy_fails_unless_the_qty_fits_its_max_spend() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(BOB, market_buy_qty(15, 20, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+  assert_error(
+    suite.submit(BOB, market_buy_qty(25, 100, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+  assert_eq!(suite.balances(BOB).quote_free, 100);
+
+  suite.submit_ok(BOB, market_buy_qty(15, 25, TimeInForce::Fok));
+  assert_eq!(suite.balances(BOB).base_free, 15);
+}
+
+#[test]
+fn buy_requires_a_qty_and_an_immediate_time_in_force() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(BOB, market_buy_qty(0, 100, TimeInForce::Ioc)),
+    "InvalidQuantity",
+  );
+  assert_error(
+    suite.submit(BOB, market_buy_qty(15, 100, TimeInForce::Gtc)),
+    "TimeInForceNotAllowed",
+  );
+}
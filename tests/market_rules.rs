@@ -0,0 +1,156 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::Uint128;
+use cw_contract::{
+  models::{OrderSide, TimeInForce, TrailingOffset},
+  msg::{ExecuteMsg, MarketConfigResponse, QueryMsg},
+};
+
+/// Instantiate a market with a tick of 0.05, a lot of 10, a min qty of 20 and
+/// a min notional of 10.
+fn setup() -> Suite {
+  let mut suite = Suite::with_msg(|msg| {
+    let market = &mut msg.quote_tokens[0];
+    market.tick_size = Some(price("0.05"));
+    market.lot_size = Some(Uint128::new(10));
+    market.min_qty = Some(Uint128::new(20));
+    market.min_notional = Some(Uint128::new(10));
+  });
+  suite.deposit_base(ALICE, 1_000);
+  suite.deposit_quote(BOB, 1_000);
+  suite
+}
+
+#[test]
+fn orders_must_follow_tick_lot_and_minimums() {
+  let mut suite = setup();
+  assert_error(suite.submit(ALICE, limit_sell(20, "1.01")), "PriceNotOnTick");
+  assert_error(suite.submit(ALICE, limit_sell(25, "1")), "QuantityNotOnLot");
+  assert_error(suite.submit(ALICE, limit_sell(10, "1")), "QuantityBelowMin");
+  assert_error(suite.submit(ALICE, limit_sell(20, "0.45")), "NotionalBelowMin");
+  suite.submit_ok(ALICE, limit_sell(20, "1.05"));
+}
+
+#[test]
+fn orders_shrunk_in_place_must_follow_lot_and_minimums() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, limit_sell(40, "0.3"));
+
+  assert_error(
+    suite.execute(ALICE, &amend(order_id, Some(35), None)),
+    "QuantityNotOnLot",
+  );
+  assert_error(
+    suite.execute(ALICE, &amend(order_id, Some(10), None)),
+    "QuantityBelowMin",
+  );
+  // 30 at 0.30 is 9, under the min notional of 10.
+  assert_error(
+    suite.execute(ALICE, &amend(order_id, Some(30), None)),
+    "NotionalBelowMin",
+  );
+
+  let order = suite.order(ALICE, order_id);
+  assert_eq!(order.qty_requested.u128(), 40);
+  assert_eq!(suite.balances(ALICE).base_locked, 40);
+}
+
+#[test]
+fn zero_limit_prices_are_rejected() {
+  let mut suite = setup();
+  assert_error(suite.submit(ALICE, limit_sell(20, "0")), "InvalidPrice");
+  assert_error(suite.submit(BOB, limit_buy(20, "0")), "InvalidPrice");
+
+  // Nothing was left in the book for later market orders to divide by.
+  assert_error(
+    suite.submit(BOB, market_buy(100, TimeInForce::Ioc)),
+    "InsufficientLiquidity",
+  );
+  assert_eq!(suite.balances(ALICE).base_free, 1_000);
+  assert_eq!(suite.balances(BOB).quote_free, 1_000);
+}
+
+#[test]
+fn zero_trigger_and_stop_limit_prices_are_rejected() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(ALICE, stop_market(OrderSide::Sell, "0", 20, TimeInForce::Ioc)),
+    "InvalidPrice",
+  );
+  assert_error(
+    suite.submit(ALICE, stop_limit(OrderSide::Sell, "1", 20, "0", TimeInForce::Gtc)),
+    "InvalidPrice",
+  );
+}
+
+#[test]
+fn invalid_trailing_offsets_are_rejected() {
+  let mut suite = setup();
+  for offset in [
+    TrailingOffset::Amount(price("0")),
+    TrailingOffset::Pct(Uint128::zero()),
+    TrailingOffset::Pct(Uint128::new(1_000_000)),
+    TrailingOffset::Pct(Uint128::new(2_000_000)),
+  ] {
+    assert_error(
+      suite.submit(ALICE, trailing_stop(OrderSide::Sell, offset, 20, TimeInForce::Ioc)),
+      "InvalidTrailingOffset",
+    );
+  }
+  assert_error(
+    suite.submit(
+      ALICE,
+      trailing_stop(
+        OrderSide::Sell,
+        TrailingOffset::Amount(price("0.03")),
+        20,
+        TimeInForce::Ioc,
+      ),
+    ),
+    "PriceNotOnTick",
+  );
+  assert_error(
+    suite.submit(
+      ALICE,
+      trailing_stop(
+        OrderSide::Sell,
+        TrailingOffset::Amount(price("0.001")),
+        20,
+        TimeInForce::Ioc,
+      ),
+    ),
+    "InvalidPrice",
+  );
+}
+
+#[test]
+fn bracket_prices_must_be_nonzero_and_on_a_tick() {
+  let mut suite = setup();
+  let bracket = |take_profit_price: &str, stop_loss_price: &str| ExecuteMsg::SubmitBracket {
+    entry: limit_buy(20, "1"),
+    take_profit_price: price(take_profit_price),
+    stop_loss_price: price(stop_loss_price),
+  };
+  assert_error(suite.execute(BOB, &bracket("0", "0")), "InvalidPrice");
+  assert_error(suite.execute(BOB, &bracket("1.5", "0")), "InvalidPrice");
+  assert_error(suite.execute(BOB, &bracket("1.51", "0.5")), "PriceNotOnTick");
+  assert_error(suite.execute(BOB, &bracket("0.5", "1.5")), "InvalidPrice");
+  suite.execute(BOB, &bracket("1.5", "0.5")).unwrap();
+}
+
+#[test]
+fn market_config_reports_the_rules() {
+  let suite = setup();
+  let config: MarketConfigResponse = suite
+    .app
+    .wrap()
+    .query_wasm_smart(&suite.contract, &QueryMsg::MarketConfig { quote: quote() })
+    .unwrap();
+
+  assert_eq!(config.price_precision, 2);
+  assert_eq!(config.tick_size, price("0.05"));
+  assert_eq!(config.lot_size.u128(), 10);
+  assert_eq!(config.min_qty.u128(), 20);
+  assert_eq!(config.min_notional.u128(), 10);
+}
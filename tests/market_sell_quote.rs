@@ -0,0 +1,99 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::{from_binary, Uint128};
+use cw_contract::{
+  models::{TimeInForce, FILLED, MATCHED},
+  msg::{MarketSellResult, OrderRequest},
+};
+
+fn market_sell_quote(
+  amount: u128,
+  max_qty: u128,
+  tif: TimeInForce,
+) -> OrderRequest {
+  OrderRequest::MarketSellQuote {
+    quote: quote(),
+    amount: Uint128::from(amount),
+    max_qty: Uint128::from(max_qty),
+    tif,
+    min_price: None,
+    min_receive: None,
+    self_trade_prevention: None,
+    client_order_id: None,
+  }
+}
+
+/// Alice rests bids of 10 at 2 and 1, and Bob holds 20 base.
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 30);
+  suite.submit_ok(ALICE, limit_buy(10, "2"));
+  suite.submit_ok(ALICE, limit_buy(10, "1"));
+  suite.deposit_base(BOB, 20);
+  suite
+}
+
+#[test]
+fn sells_enough_to_raise_the_amount_and_reports_it() {
+  let mut suite = setup();
+  let resp = suite.submit(BOB, market_sell_quote(25, 20, TimeInForce::Ioc)).unwrap();
+
+  let result: MarketSellResult = from_binary(resp.data.as_ref().unwrap()).unwrap();
+  assert_eq!(result.qty_sold.u128(), 15);
+  assert_eq!(result.quote_received.u128(), 25);
+  assert_eq!(suite.order(BOB, result.order_id.u64()).status, FILLED);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 5,
+      quote_free: 25,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn sells_the_least_qty_that_covers_the_amount() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 30);
+  suite.submit_ok(ALICE, limit_buy(10, "3"));
+  suite.deposit_base(BOB, 10);
+
+  let resp = suite.submit(BOB, market_sell_quote(7, 10, TimeInForce::Ioc)).unwrap();
+
+  // Selling 2 raises only 6, while 3 raises 9.
+  let result: MarketSellResult = from_binary(resp.data.as_ref().unwrap()).unwrap();
+  assert_eq!(result.qty_sold.u128(), 3);
+  assert_eq!(result.quote_received.u128(), 9);
+  assert_eq!(suite.balances(BOB).base_free, 7);
+}
+
+#[test]
+fn ioc_sell_stops_at_its_max_qty() {
+  let mut suite = setup();
+  let resp = suite.submit(BOB, market_sell_quote(25, 12, TimeInForce::Ioc)).unwrap();
+
+  let result: MarketSellResult = from_binary(resp.data.as_ref().unwrap()).unwrap();
+  assert_eq!(result.qty_sold.u128(), 12);
+  assert_eq!(result.quote_received.u128(), 22);
+  assert_eq!(suite.order(BOB, result.order_id.u64()).status, MATCHED);
+  assert_eq!(suite.balances(BOB).base_free, 8);
+}
+
+#[test]
+fn fok_sell_fails_unless_it_raises_the_amount() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(BOB, market_sell_quote(25, 12, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+  assert_error(
+    suite.submit(BOB, market_sell_quote(31, 20, TimeInForce::Fok)),
+    "InsufficientLiquidity",
+  );
+  assert_eq!(suite.balances(BOB).base_free, 20);
+
+  suite.submit_ok(BOB, market_sell_quote(25, 20, TimeInForce::Fok));
+  assert_eq!(suite.balances(BOB).quote_free, 25);
+}
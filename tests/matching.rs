@@ -0,0 +1,95 @@
+mod common;
+
+use common::*;
+use cw_contract::models::{TimeInForce, FILLED};
+
+#[test]
+fn market_buy_matches_lowest_asks_first() {
+  let mut suite = Suite::new();
+  suite.transfer_base(ALICE, 30);
+  let high_ask = order_id(&suite.send_base(ALICE, 10, &limit_sell(10, "3")).unwrap());
+  let low_ask = order_id(&suite.send_base(ALICE, 10, &limit_sell(10, "1")).unwrap());
+  let mid_ask = order_id(&suite.send_base(ALICE, 10, &limit_sell(10, "2")).unwrap());
+
+  // Spend enough to buy the asks at 1 and 2 in full, with 5 left over.
+  let resp = suite
+    .submit_with_funds(BOB, market_buy(35, TimeInForce::Ioc), 35)
+    .unwrap();
+  let order_id = order_id(&resp);
+
+  assert_eq!(suite.order(ALICE, low_ask).status, FILLED);
+  assert_eq!(suite.order(ALICE, mid_ask).status, FILLED);
+  assert_eq!(suite.order(ALICE, high_ask).qty_matched.u128(), 1);
+  assert_eq!(suite.order(BOB, order_id).qty_matched.u128(), 21);
+  assert_eq!(suite.balances(BOB).base_free, 21);
+  assert_eq!(suite.balances(ALICE).quote_free, 33);
+  // What's left of the balance after buying one more at 3 is refunded.
+  assert_eq!(suite.quote_balance(BOB), QUOTE_SUPPLY - 33);
+}
+
+#[test]
+fn market_sell_matches_highest_bids_first() {
+  let mut suite = Suite::new();
+  let low_bid = order_id(&suite.submit_with_funds(ALICE, limit_buy(10, "1"), 10).unwrap());
+  let high_bid = order_id(&suite.submit_with_funds(ALICE, limit_buy(10, "3"), 30).unwrap());
+  let mid_bid = order_id(&suite.submit_with_funds(ALICE, limit_buy(10, "2"), 20).unwrap());
+
+  suite.transfer_base(BOB, 15);
+  let resp = suite.send_base(BOB, 15, &market_sell(15, TimeInForce::Ioc)).unwrap();
+  let order_id = order_id(&resp);
+
+  assert_eq!(suite.order(ALICE, high_bid).status, FILLED);
+  assert_eq!(suite.order(ALICE, mid_bid).qty_matched.u128(), 5);
+  assert_eq!(suite.order(ALICE, low_bid).qty_matched.u128(), 0);
+  assert_eq!(suite.order(BOB, order_id).status, FILLED);
+  // Bidders are credited only the qty matched against them.
+  assert_eq!(suite.balances(ALICE).base_free, 15);
+  assert_eq!(suite.balances(BOB).quote_free, 30 + 10);
+}
+
+#[test]
+fn sell_matches_the_oldest_bid_at_a_price_first() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 10);
+  suite.deposit_quote(CAROL, 30);
+  let carol_first_bid = suite.submit_ok(CAROL, limit_buy(10, "1"));
+  let alice_bid = suite.submit_ok(ALICE, limit_buy(10, "1"));
+  let carol_last_bid = suite.submit_ok(CAROL, limit_buy(10, "1"));
+
+  suite.deposit_base(BOB, 15);
+  suite.submit_ok(BOB, limit_sell(15, "1"));
+
+  assert_eq!(suite.order(CAROL, carol_first_bid).status, FILLED);
+  assert_eq!(suite.order(ALICE, alice_bid).qty_matched.u128(), 5);
+  assert_eq!(suite.order(CAROL, carol_last_bid).qty_matched.u128(), 0);
+}
+
+#[test]
+fn market_sell_matches_the_oldest_bid_at_a_price_first() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 10);
+  suite.deposit_quote(CAROL, 10);
+  let carol_bid = suite.submit_ok(CAROL, limit_buy(10, "1"));
+  let alice_bid = suite.submit_ok(ALICE, limit_buy(10, "1"));
+
+  suite.deposit_base(BOB, 15);
+  suite.submit_ok(BOB, market_sell(15, TimeInForce::Ioc));
+
+  assert_eq!(suite.order(CAROL, carol_bid).status, FILLED);
+  assert_eq!(suite.order(ALICE, alice_bid).qty_matched.u128(), 5);
+}
+
+#[test]
+fn buy_matches_the_oldest_ask_at_a_price_first() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_base(CAROL, 10);
+  let carol_ask = suite.submit_ok(CAROL, limit_sell(10, "1"));
+  let alice_ask = suite.submit_ok(ALICE, limit_sell(10, "1"));
+
+  suite.deposit_quote(BOB, 15);
+  suite.submit_ok(BOB, limit_buy(15, "1"));
+
+  assert_eq!(suite.order(CAROL, carol_ask).status, FILLED);
+  assert_eq!(suite.order(ALICE, alice_ask).qty_matched.u128(), 5);
+}
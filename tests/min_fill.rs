@@ -0,0 +1,89 @@
+mod common;
+
+use common::*;
+use cw_contract::models::{CREATED, FILLED};
+
+fn all_or_none() -> LimitOpts {
+  LimitOpts {
+    all_or_none: Some(true),
+    ..Default::default()
+  }
+}
+
+fn min_fill(qty: u128) -> LimitOpts {
+  LimitOpts {
+    min_fill_qty: Some(qty),
+    ..Default::default()
+  }
+}
+
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 20);
+  suite.deposit_base(CAROL, 20);
+  suite.deposit_quote(BOB, 100);
+  suite
+}
+
+#[test]
+fn resting_all_or_none_order_is_skipped_unless_filled_in_full() {
+  let mut suite = setup();
+  let aon_id = suite.submit_ok(ALICE, limit_sell_with(20, "1", all_or_none()));
+  let carol_id = suite.submit_ok(CAROL, limit_sell(10, "1"));
+
+  suite.submit_ok(BOB, limit_buy(10, "1"));
+  assert_eq!(suite.order(CAROL, carol_id).status, FILLED);
+  assert!(suite.order(ALICE, aon_id).qty_matched.is_zero());
+
+  suite.submit_ok(BOB, limit_buy(20, "1"));
+  assert_eq!(suite.order(ALICE, aon_id).status, FILLED);
+}
+
+#[test]
+fn all_or_none_taker_matches_nothing_unless_filled_in_full() {
+  let mut suite = setup();
+  let ask_id = suite.submit_ok(ALICE, limit_sell(10, "1"));
+
+  let bid_id = suite.submit_ok(BOB, limit_buy_with(20, "1", all_or_none()));
+
+  assert_eq!(suite.order(BOB, bid_id).status, CREATED);
+  assert!(suite.order(BOB, bid_id).qty_matched.is_zero());
+  assert!(suite.order(ALICE, ask_id).qty_matched.is_zero());
+  assert_eq!(suite.balances(BOB).quote_locked, 20);
+}
+
+#[test]
+fn resting_min_fill_qty_skips_smaller_takers() {
+  let mut suite = setup();
+  let ask_id = suite.submit_ok(ALICE, limit_sell_with(20, "1", min_fill(5)));
+
+  suite.submit_ok(BOB, limit_buy(3, "1"));
+  assert!(suite.order(ALICE, ask_id).qty_matched.is_zero());
+
+  suite.submit_ok(BOB, limit_buy(5, "1"));
+  assert_eq!(suite.order(ALICE, ask_id).qty_matched.u128(), 5);
+}
+
+#[test]
+fn taker_min_fill_qty_skips_smaller_makers() {
+  let mut suite = setup();
+  let small_ask_id = suite.submit_ok(ALICE, limit_sell(3, "1"));
+  let large_ask_id = suite.submit_ok(CAROL, limit_sell(10, "1"));
+
+  let bid_id = suite.submit_ok(BOB, limit_buy_with(10, "1", min_fill(5)));
+
+  assert!(suite.order(ALICE, small_ask_id).qty_matched.is_zero());
+  assert_eq!(suite.order(CAROL, large_ask_id).status, FILLED);
+  assert_eq!(suite.order(BOB, bid_id).status, FILLED);
+}
+
+#[test]
+fn min_fill_qty_must_be_within_the_order_qty() {
+  let mut suite = setup();
+  for min_fill_qty in [0, 11] {
+    assert_error(
+      suite.submit(BOB, limit_buy_with(10, "1", min_fill(min_fill_qty))),
+      "InvalidQuantity",
+    );
+  }
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{OrderSide, TimeInForce, CANCELED, CREATED, FILLED},
+  msg::{ExecuteMsg, OrderRequest},
+};
+
+fn bracket(entry: OrderRequest) -> ExecuteMsg {
+  ExecuteMsg::SubmitBracket {
+    entry,
+    take_profit_price: price("15"),
+    stop_loss_price: price("5"),
+  }
+}
+
+fn submit_oco(
+  suite: &mut Suite,
+  sender: &str,
+  a: OrderRequest,
+  b: OrderRequest,
+) -> Vec<u64> {
+  let resp = suite.execute(sender, &ExecuteMsg::SubmitOco { a, b }).unwrap();
+  attr_value(&resp, "order_ids")
+    .split(',')
+    .map(|id| id.parse().unwrap())
+    .collect()
+}
+
+#[test]
+fn filled_oco_order_cancels_its_sibling() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_quote(BOB, 1_000);
+  let order_ids = submit_oco(
+    &mut suite,
+    BOB,
+    limit_buy(10, "9"),
+    stop_market(OrderSide::Buy, "12", 100, TimeInForce::Ioc),
+  );
+
+  suite.submit_ok(ALICE, limit_sell(10, "9"));
+
+  assert_eq!(suite.order(BOB, order_ids[1]).status, CANCELED);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 10,
+      quote_free: 910,
+      ..Balances::default()
+    }
+  );
+}
+
+#[test]
+fn partially_filled_oco_order_resizes_its_sibling() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 4);
+  suite.deposit_quote(BOB, 1_000);
+  let order_ids = submit_oco(
+    &mut suite,
+    BOB,
+    limit_buy(10, "9"),
+    stop_limit(OrderSide::Buy, "12", 20, "13", TimeInForce::Gtc),
+  );
+
+  suite.submit_ok(ALICE, limit_sell(4, "9"));
+
+  assert_eq!(suite.order(BOB, order_ids[1]).qty_requested.u128(), 12);
+}
+
+#[test]
+fn canceling_oco_order_cancels_its_sibling() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 1_000);
+  let order_ids = submit_oco(
+    &mut suite,
+    BOB,
+    limit_buy(10, "9"),
+    stop_market(OrderSide::Buy, "12", 100, TimeInForce::Ioc),
+  );
+  assert_eq!(suite.balances(BOB).quote_locked, 90);
+
+  suite.execute(BOB, &cancel(order_ids[0])).unwrap();
+
+  assert_eq!(suite.order(BOB, order_ids[0]).status, CANCELED);
+  assert_eq!(suite.order(BOB, order_ids[1]).status, CANCELED);
+  assert_eq!(suite.balances(BOB).quote_free, 1_000);
+  assert_eq!(suite.balances(BOB).quote_locked, 0);
+}
+
+#[test]
+fn amended_oco_order_stays_in_its_group() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_quote(BOB, 1_000);
+  let order_ids = submit_oco(
+    &mut suite,
+    BOB,
+    limit_buy(10, "9"),
+    stop_market(OrderSide::Buy, "12", 100, TimeInForce::Ioc),
+  );
+
+  suite.execute(BOB, &amend(order_ids[0], None, Some("10"))).unwrap();
+  assert_eq!(suite.order(BOB, order_ids[1]).status, CREATED);
+
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+
+  assert_eq!(suite.order(BOB, order_ids[0]).status, FILLED);
+  assert_eq!(suite.order(BOB, order_ids[1]).status, CANCELED);
+}
+
+#[test]
+fn amended_oco_order_matching_on_arrival_resizes_its_sibling() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 4);
+  suite.deposit_quote(BOB, 1_000);
+  let order_ids = submit_oco(
+    &mut suite,
+    BOB,
+    limit_buy(10, "9"),
+    stop_limit(OrderSide::Buy, "12", 20, "13", TimeInForce::Gtc),
+  );
+  suite.submit_ok(ALICE, limit_sell(4, "10"));
+
+  suite.execute(BOB, &amend(order_ids[0], None, Some("10"))).unwrap();
+
+  assert_eq!(suite.order(BOB, order_ids[0]).qty_matched.u128(), 4);
+  assert_eq!(suite.order(BOB, order_ids[1]).qty_requested.u128(), 12);
+}
+
+#[test]
+fn contingent_stop_never_draws_escrow_it_lacks() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 100);
+  suite.deposit_quote(BOB, 1_000);
+  suite.deposit_base(CAROL, 10);
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+  suite.submit_ok(BOB, limit_buy(10, "10"));
+
+  // The stop buy needs quote, which Carol doesn't have.
+  let order_ids = submit_oco(
+    &mut suite,
+    CAROL,
+    limit_sell(10, "20"),
+    stop_market(OrderSide::Buy, "11", 500, TimeInForce::Ioc),
+  );
+  assert_eq!(suite.order(CAROL, order_ids[1]).status, CREATED);
+
+  suite.submit_ok(ALICE, limit_sell(10, "11"));
+  let resp = suite.submit(BOB, limit_buy(10, "11")).unwrap();
+
+  // The stop is canceled without touching the rest of its group.
+  assert_eq!(
+    event_attr_values(&resp, "cancel_stop_order", "reason"),
+    vec!["InsufficientBalance".to_owned()]
+  );
+  assert_eq!(suite.order(CAROL, order_ids[1]).status, CANCELED);
+  assert_eq!(suite.order(CAROL, order_ids[0]).status, CREATED);
+  assert_eq!(
+    suite.balances(CAROL),
+    Balances {
+      base_locked: 10,
+      ..Balances::default()
+    }
+  );
+}
+
+#[test]
+fn bracket_entry_filled_as_maker_places_exits() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 1_000);
+  suite.deposit_base(ALICE, 100);
+  suite.execute(BOB, &bracket(limit_buy(10, "10"))).unwrap();
+
+  suite.submit_ok(ALICE, market_sell(10, TimeInForce::Ioc));
+
+  // The take-profit exit escrows the base bought, and the stop-loss exit
+  // draws it only when triggered.
+  assert_eq!(suite.balances(BOB).base_locked, 10);
+  assert_eq!(suite.balances(BOB).base_free, 0);
+}
+
+#[test]
+fn amended_bracket_entry_matching_on_arrival_places_exits() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 1_000);
+  suite.deposit_base(ALICE, 10);
+  let resp = suite.execute(BOB, &bracket(limit_buy(10, "9"))).unwrap();
+  let entry_id: u64 = attr_value(&resp, "order_id").parse().unwrap();
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+
+  suite.execute(BOB, &amend(entry_id, None, Some("10"))).unwrap();
+
+  assert_eq!(suite.order(BOB, entry_id).status, FILLED);
+  assert_eq!(suite.balances(BOB).base_locked, 10);
+  assert_eq!(suite.balances(BOB).base_free, 0);
+}
+
+#[test]
+fn bracket_exits_placed_per_order_are_capped() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 2_000);
+  suite.deposit_base(ALICE, 1_000);
+  suite.deposit_quote(CAROL, 1_000);
+  for _ in 0..12 {
+    suite.execute(BOB, &bracket(limit_buy(10, "10"))).unwrap();
+  }
+
+  suite.submit_ok(ALICE, market_sell(120, TimeInForce::Ioc));
+  assert_eq!(suite.balances(BOB).base_free, 20);
+  assert_eq!(suite.balances(BOB).base_locked, 100);
+
+  // The exits left queued are placed after the next order.
+  suite.submit_ok(CAROL, limit_buy(10, "1"));
+  assert_eq!(suite.balances(BOB).base_free, 0);
+  assert_eq!(suite.balances(BOB).base_locked, 120);
+}
+
+#[test]
+fn bracket_rejects_exits_on_the_wrong_side_of_each_other() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 1_000);
+  assert_error(
+    suite.execute(
+      BOB,
+      &ExecuteMsg::SubmitBracket {
+        entry: limit_buy(10, "10"),
+        take_profit_price: price("5"),
+        stop_loss_price: price("15"),
+      },
+    ),
+    "InvalidPrice",
+  );
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{PostOnly, TimeInForce, CREATED},
+  msg::OrderRequest,
+};
+
+fn post_only_buy(
+  qty: u128,
+  price_str: &str,
+  tif: TimeInForce,
+  post_only: PostOnly,
+) -> OrderRequest {
+  limit_buy_with(
+    qty,
+    price_str,
+    LimitOpts {
+      tif: Some(tif),
+      post_only: Some(post_only),
+      ..Default::default()
+    },
+  )
+}
+
+fn post_only_sell(
+  qty: u128,
+  price_str: &str,
+  post_only: PostOnly,
+) -> OrderRequest {
+  limit_sell_with(
+    qty,
+    price_str,
+    LimitOpts {
+      post_only: Some(post_only),
+      ..Default::default()
+    },
+  )
+}
+
+/// Carol rests an ask of 10 at 20 and a bid of 10 at 10.
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(CAROL, 10);
+  suite.deposit_quote(CAROL, 100);
+  suite.submit_ok(CAROL, limit_sell(10, "20"));
+  suite.submit_ok(CAROL, limit_buy(10, "10"));
+  suite.deposit_quote(ALICE, 1_000);
+  suite.deposit_base(ALICE, 10);
+  suite
+}
+
+#[test]
+fn reject_rests_an_order_that_does_not_cross() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, post_only_buy(10, "15", TimeInForce::Gtc, PostOnly::Reject));
+
+  let order = suite.order(ALICE, order_id);
+  assert_eq!(order.status, CREATED);
+  assert_eq!(order.limit_price.u128(), 1500);
+  assert_eq!(suite.balances(ALICE).quote_locked, 150);
+}
+
+#[test]
+fn reject_fails_an_order_that_would_match() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(ALICE, post_only_buy(10, "20", TimeInForce::Gtc, PostOnly::Reject)),
+    "PostOnlyWouldMatch",
+  );
+  assert_error(
+    suite.submit(ALICE, post_only_sell(10, "10", PostOnly::Reject)),
+    "PostOnlyWouldMatch",
+  );
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 10,
+      quote_free: 1_000,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn amended_order_stays_post_only() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, post_only_buy(10, "15", TimeInForce::Gtc, PostOnly::Reject));
+
+  assert_error(
+    suite.execute(ALICE, &amend(order_id, None, Some("20"))),
+    "PostOnlyWouldMatch",
+  );
+
+  let order = suite.order(ALICE, order_id);
+  assert_eq!(order.status, CREATED);
+  assert_eq!(order.limit_price.u128(), 1500);
+  assert_eq!(suite.balances(ALICE).quote_locked, 150);
+}
+
+#[test]
+fn reprice_rests_a_buy_one_tick_below_the_best_ask() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, post_only_buy(10, "30", TimeInForce::Gtc, PostOnly::Reprice));
+
+  let order = suite.order(ALICE, order_id);
+  assert_eq!(order.status, CREATED);
+  assert_eq!(order.limit_price.u128(), 1999);
+  assert!(order.qty_matched.is_zero());
+  // Only the escrow needed at the new price stays locked.
+  let balances = suite.balances(ALICE);
+  assert_eq!(balances.quote_locked, 199);
+  assert_eq!(balances.quote_free, 801);
+}
+
+#[test]
+fn reprice_rests_a_sell_one_tick_above_the_best_bid() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(ALICE, post_only_sell(10, "5", PostOnly::Reprice));
+
+  let order = suite.order(ALICE, order_id);
+  assert_eq!(order.status, CREATED);
+  assert_eq!(order.limit_price.u128(), 1001);
+  assert_eq!(suite.balances(ALICE).base_locked, 10);
+}
+
+#[test]
+fn post_only_requires_a_resting_time_in_force() {
+  let mut suite = setup();
+  assert_error(
+    suite.submit(ALICE, post_only_buy(10, "15", TimeInForce::Ioc, PostOnly::Reject)),
+    "TimeInForceNotAllowed",
+  );
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+
+fn with_precision(precision: u8) -> Suite {
+  Suite::with_msg(|msg| msg.quote_tokens[0].price_precision = precision)
+}
+
+#[test]
+fn sub_unit_prices_round_quote_amounts_down() {
+  let mut suite = with_precision(4);
+  suite.deposit_quote(BOB, 100);
+  // 30 at 0.3333 is 9.999, of which only whole quote units are escrowed.
+  let bid_id = suite.submit_ok(BOB, limit_buy(30, "0.3333"));
+  assert_eq!(suite.order(BOB, bid_id).limit_price.u128(), 3333);
+  assert_eq!(suite.balances(BOB).quote_locked, 9);
+
+  suite.deposit_base(ALICE, 30);
+  suite.submit_ok(ALICE, limit_sell(30, "0.3333"));
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      quote_free: 9,
+      ..Default::default()
+    }
+  );
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 30,
+      quote_free: 91,
+      ..Default::default()
+    }
+  );
+  assert_eq!(suite.contract_quote_balance(), 100);
+}
+
+#[test]
+fn price_cannot_exceed_the_market_precision() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(BOB, 100);
+  assert_error(suite.submit(BOB, limit_buy(10, "1.234")), "InvalidPrice");
+  suite.submit_ok(BOB, limit_buy(10, "1.23"));
+}
+
+#[test]
+fn depth_reports_decimal_prices() {
+  let mut suite = with_precision(4);
+  suite.deposit_quote(BOB, 100);
+  suite.submit_ok(BOB, limit_buy(30, "0.3333"));
+
+  let depth = suite.depth();
+  assert_eq!(depth.bids[0].price, price("0.3333"));
+  assert_eq!(depth.bids[0].qty.u128(), 30);
+}
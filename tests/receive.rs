@@ -0,0 +1,99 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{TimeInForce, CREATED},
+  msg::ReceiveMsg,
+};
+
+#[test]
+fn cw20_limit_sell_escrows_the_sent_qty() {
+  let mut suite = Suite::new();
+  suite.transfer_base(ALICE, 10);
+
+  let resp = suite.send_base(ALICE, 10, &limit_sell(10, "1")).unwrap();
+
+  assert_eq!(suite.order(ALICE, order_id(&resp)).status, CREATED);
+  assert_eq!(suite.base_balance(ALICE), 0);
+  assert_eq!(suite.base_balance(suite.contract.as_str()), 10);
+}
+
+#[test]
+fn cw20_market_sell_refunds_the_unmatched_qty() {
+  let mut suite = Suite::new();
+  suite.submit_with_funds(BOB, limit_buy(4, "1"), 4).unwrap();
+  suite.transfer_base(ALICE, 10);
+
+  suite.send_base(ALICE, 10, &market_sell(10, TimeInForce::Ioc)).unwrap();
+
+  assert_eq!(suite.base_balance(ALICE), 6);
+  assert_eq!(suite.balances(ALICE).quote_free, 4);
+  assert_eq!(suite.balances(BOB).base_free, 4);
+}
+
+#[test]
+fn cw20_submit_requires_the_exact_qty() {
+  let mut suite = Suite::new();
+  suite.transfer_base(ALICE, 10);
+
+  assert_error(suite.send_base(ALICE, 9, &limit_sell(10, "1")), "InsufficientFunds");
+  assert_error(suite.send_base(ALICE, 10, &limit_sell(9, "1")), "ExcessFunds");
+  assert_eq!(suite.base_balance(ALICE), 10);
+}
+
+#[test]
+fn wrapped_order_request_submits_an_order() {
+  let mut suite = Suite::new();
+  suite.transfer_base(ALICE, 10);
+
+  let resp = suite
+    .send_base(ALICE, 10, &ReceiveMsg::Submit(limit_sell(10, "1")))
+    .unwrap();
+
+  assert_eq!(suite.order(ALICE, order_id(&resp)).status, CREATED);
+  assert_eq!(suite.base_balance(suite.contract.as_str()), 10);
+}
+
+#[test]
+fn deposit_credits_the_internal_balance() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_quote(ALICE, 20);
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 10,
+      quote_free: 20,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn orders_without_funds_draw_from_the_deposited_balance() {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_quote(BOB, 30);
+
+  suite.submit_ok(ALICE, limit_sell(10, "2"));
+  suite.submit_ok(BOB, limit_buy(10, "2"));
+
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 0,
+      quote_free: 20,
+      ..Default::default()
+    }
+  );
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 10,
+      quote_free: 10,
+      ..Default::default()
+    }
+  );
+  assert_error(suite.submit(ALICE, limit_sell(1, "2")), "InsufficientBalance");
+}
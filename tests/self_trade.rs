@@ -0,0 +1,157 @@
+mod common;
+
+use common::*;
+use cw_contract::{
+  models::{SelfTradePrevention, CANCELED, CREATED, FILLED},
+  msg::OrderRequest,
+};
+
+fn limit_buy_stp(
+  qty: u128,
+  stp: SelfTradePrevention,
+) -> OrderRequest {
+  limit_buy_with(
+    qty,
+    "1",
+    LimitOpts {
+      self_trade_prevention: Some(stp),
+      ..Default::default()
+    },
+  )
+}
+
+/// Alice and then Carol rest asks of 10 at 1, and Alice holds quote to buy
+/// from them.
+fn setup() -> (Suite, u64, u64) {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 10);
+  suite.deposit_base(CAROL, 10);
+  let alice_ask = suite.submit_ok(ALICE, limit_sell(10, "1"));
+  let carol_ask = suite.submit_ok(CAROL, limit_sell(10, "1"));
+  suite.deposit_quote(ALICE, 100);
+  (suite, alice_ask, carol_ask)
+}
+
+#[test]
+fn cancel_newest_cancels_the_taker() {
+  let (mut suite, alice_ask, carol_ask) = setup();
+
+  let bid_id = suite.submit_ok(ALICE, limit_buy_stp(10, SelfTradePrevention::CancelNewest));
+
+  assert_eq!(suite.order(ALICE, bid_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, alice_ask).status, CREATED);
+  assert_eq!(suite.order(CAROL, carol_ask).status, CREATED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_locked: 10,
+      quote_free: 100,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn cancel_oldest_cancels_the_maker_and_keeps_matching() {
+  let (mut suite, alice_ask, carol_ask) = setup();
+
+  let bid_id = suite.submit_ok(ALICE, limit_buy_stp(10, SelfTradePrevention::CancelOldest));
+
+  assert_eq!(suite.order(ALICE, bid_id).status, FILLED);
+  assert_eq!(suite.order(ALICE, alice_ask).status, CANCELED);
+  assert_eq!(suite.order(CAROL, carol_ask).status, FILLED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 20,
+      quote_free: 90,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn cancel_both_cancels_the_maker_and_taker() {
+  let (mut suite, alice_ask, carol_ask) = setup();
+
+  let bid_id = suite.submit_ok(ALICE, limit_buy_stp(10, SelfTradePrevention::CancelBoth));
+
+  assert_eq!(suite.order(ALICE, bid_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, alice_ask).status, CANCELED);
+  assert_eq!(suite.order(CAROL, carol_ask).status, CREATED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 10,
+      quote_free: 100,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn decrement_and_cancel_shrinks_the_larger_order() {
+  let (mut suite, alice_ask, carol_ask) = setup();
+
+  // The taker is smaller, so the maker is decremented and the taker canceled.
+  let bid_id = suite.submit_ok(ALICE, limit_buy_stp(4, SelfTradePrevention::DecrementAndCancel));
+  assert_eq!(suite.order(ALICE, bid_id).status, CANCELED);
+  let ask = suite.order(ALICE, alice_ask);
+  assert_eq!(ask.status, CREATED);
+  assert_eq!(ask.qty_requested.u128(), 6);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 4,
+      base_locked: 6,
+      quote_free: 100,
+      ..Default::default()
+    }
+  );
+
+  // The maker is smaller, so it's canceled and the taker decremented by its
+  // qty before matching on.
+  let bid_id = suite.submit_ok(ALICE, limit_buy_stp(11, SelfTradePrevention::DecrementAndCancel));
+  assert_eq!(suite.order(ALICE, alice_ask).status, CANCELED);
+  let bid = suite.order(ALICE, bid_id);
+  assert_eq!(bid.status, FILLED);
+  assert_eq!(bid.qty_matched.u128(), 5);
+  assert_eq!(suite.order(CAROL, carol_ask).qty_matched.u128(), 5);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_free: 15,
+      quote_free: 95,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn amended_order_keeps_its_self_trade_prevention() {
+  let (mut suite, alice_ask, _) = setup();
+  let bid_id = suite.submit_ok(
+    ALICE,
+    limit_buy_with(
+      10,
+      "0.5",
+      LimitOpts {
+        self_trade_prevention: Some(SelfTradePrevention::CancelNewest),
+        ..Default::default()
+      },
+    ),
+  );
+
+  suite.execute(ALICE, &amend(bid_id, None, Some("1"))).unwrap();
+
+  assert_eq!(suite.order(ALICE, bid_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, alice_ask).status, CREATED);
+  assert_eq!(
+    suite.balances(ALICE),
+    Balances {
+      base_locked: 10,
+      quote_free: 100,
+      ..Default::default()
+    }
+  );
+}
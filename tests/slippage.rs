@@ -0,0 +1,101 @@
+mod common;
+
+use common::*;
+use cw_contract::models::{TimeInForce, MATCHED};
+
+fn price_limit(price_str: &'static str) -> MarketOpts {
+  MarketOpts {
+    price_limit: Some(price_str),
+    ..Default::default()
+  }
+}
+
+fn min_receive(amount: u128) -> MarketOpts {
+  MarketOpts {
+    min_receive: Some(amount),
+    ..Default::default()
+  }
+}
+
+/// Alice rests asks of 10 at 10 and 30, and Bob holds 400 quote.
+fn setup_asks() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 20);
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+  suite.submit_ok(ALICE, limit_sell(10, "30"));
+  suite.deposit_quote(BOB, 400);
+  suite
+}
+
+#[test]
+fn unprotected_market_buy_walks_the_book() {
+  let mut suite = setup_asks();
+  suite.submit_ok(BOB, market_buy(400, TimeInForce::Ioc));
+
+  assert_eq!(suite.balances(BOB).base_free, 20);
+  assert_eq!(suite.balances(BOB).quote_free, 0);
+}
+
+#[test]
+fn ioc_market_buy_stops_at_its_max_price() {
+  let mut suite = setup_asks();
+  let order_id = suite.submit_ok(BOB, market_buy_with(400, TimeInForce::Ioc, price_limit("20")));
+
+  assert_eq!(suite.order(BOB, order_id).status, MATCHED);
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      base_free: 10,
+      quote_free: 300,
+      ..Default::default()
+    }
+  );
+}
+
+#[test]
+fn fok_market_buy_fails_at_its_max_price() {
+  let mut suite = setup_asks();
+  assert_error(
+    suite.submit(BOB, market_buy_with(400, TimeInForce::Fok, price_limit("20"))),
+    "InsufficientLiquidity",
+  );
+  assert_eq!(suite.balances(BOB).quote_free, 400);
+}
+
+#[test]
+fn market_buy_fails_below_its_min_receive() {
+  let mut suite = setup_asks();
+  assert_error(
+    suite.submit(BOB, market_buy_with(200, TimeInForce::Ioc, min_receive(14))),
+    "SlippageExceeded",
+  );
+  suite.submit_ok(BOB, market_buy_with(200, TimeInForce::Ioc, min_receive(13)));
+  assert_eq!(suite.balances(BOB).base_free, 13);
+}
+
+#[test]
+fn market_sell_fails_below_its_min_receive() {
+  let mut suite = Suite::new();
+  suite.deposit_quote(ALICE, 300);
+  suite.submit_ok(ALICE, limit_buy(10, "20"));
+  suite.submit_ok(ALICE, limit_buy(10, "10"));
+  suite.deposit_base(BOB, 20);
+
+  assert_error(
+    suite.submit(BOB, market_sell_with(20, TimeInForce::Ioc, min_receive(310))),
+    "SlippageExceeded",
+  );
+  assert_error(
+    suite.submit(BOB, market_sell_with(20, TimeInForce::Fok, price_limit("15"))),
+    "InsufficientLiquidity",
+  );
+  suite.submit_ok(BOB, market_sell_with(20, TimeInForce::Ioc, min_receive(300)));
+
+  assert_eq!(
+    suite.balances(BOB),
+    Balances {
+      quote_free: 300,
+      ..Default::default()
+    }
+  );
+}
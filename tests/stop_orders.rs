@@ -0,0 +1,116 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::Uint128;
+use cw_contract::models::{OrderSide, TimeInForce, CANCELED, CREATED, EXPIRED, FILLED};
+
+/// Alice and Bob trade 10 at 10, setting the last price.
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 1_000);
+  suite.deposit_quote(BOB, 1_000);
+  suite.deposit_base(CAROL, 100);
+  suite.deposit_quote(CAROL, 1_000);
+  suite.submit_ok(ALICE, limit_sell(10, "10"));
+  suite.submit_ok(BOB, limit_buy(10, "10"));
+  suite
+}
+
+#[test]
+fn stop_sell_triggers_into_bids() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(CAROL, stop_market(OrderSide::Sell, "9", 10, TimeInForce::Ioc));
+
+  suite.submit_ok(BOB, limit_buy(20, "9"));
+  suite.submit_ok(ALICE, market_sell(10, TimeInForce::Ioc));
+
+  assert_eq!(suite.order(CAROL, order_id).status, FILLED);
+  assert_eq!(suite.balances(CAROL).base_free, 90);
+  assert_eq!(suite.balances(CAROL).quote_free, 1_000 + 90);
+}
+
+#[test]
+fn stop_without_liquidity_is_canceled_with_reason() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(CAROL, stop_market(OrderSide::Sell, "9", 10, TimeInForce::Ioc));
+  assert_eq!(suite.balances(CAROL).base_locked, 10);
+
+  // The trade at 9 takes the only bid, leaving nothing for the stop.
+  suite.submit_ok(BOB, limit_buy(10, "9"));
+  let resp = suite.submit(ALICE, market_sell(10, TimeInForce::Ioc)).unwrap();
+
+  assert_eq!(
+    event_attr_values(&resp, "cancel_stop_order", "order_id"),
+    vec![order_id.to_string()]
+  );
+  assert_eq!(
+    event_attr_values(&resp, "cancel_stop_order", "reason"),
+    vec!["InsufficientLiquidity".to_owned()]
+  );
+  assert_eq!(suite.order(CAROL, order_id).status, CANCELED);
+  assert_eq!(suite.balances(CAROL).base_free, 100);
+  assert_eq!(suite.balances(CAROL).base_locked, 0);
+}
+
+#[test]
+fn failed_trigger_leaves_book_untouched() {
+  let mut suite = setup();
+  let order_id = suite.submit_ok(CAROL, stop_market(OrderSide::Buy, "11", 1_000, TimeInForce::Fok));
+  let ask_id = suite.submit_ok(ALICE, limit_sell(5, "12"));
+
+  // The trade at 11 triggers the stop, which can't spend its whole balance.
+  suite.submit_ok(ALICE, limit_sell(10, "11"));
+  let resp = suite.submit(BOB, limit_buy(10, "11")).unwrap();
+
+  assert_eq!(
+    event_attr_values(&resp, "cancel_stop_order", "reason"),
+    vec!["InsufficientLiquidity".to_owned()]
+  );
+  assert_eq!(suite.order(CAROL, order_id).status, CANCELED);
+  assert_eq!(suite.order(ALICE, ask_id).status, CREATED);
+  assert_eq!(suite.order(ALICE, ask_id).qty_matched, Uint128::zero());
+  assert_eq!(suite.balances(CAROL).quote_free, 1_000);
+  assert_eq!(suite.balances(CAROL).base_free, 100);
+}
+
+#[test]
+fn stops_already_reached_are_rejected() {
+  let mut suite = setup();
+  for (side, trigger_price) in [
+    (OrderSide::Buy, "10"),
+    (OrderSide::Buy, "9"),
+    (OrderSide::Sell, "10"),
+    (OrderSide::Sell, "11"),
+  ] {
+    assert_error(
+      suite.submit(CAROL, stop_market(side, trigger_price, 10, TimeInForce::Ioc)),
+      "TriggerPriceReached",
+    );
+  }
+  assert_error(
+    suite.submit(CAROL, stop_limit(OrderSide::Buy, "9", 10, "10", TimeInForce::Gtc)),
+    "TriggerPriceReached",
+  );
+  assert_eq!(suite.balances(CAROL).quote_free, 1_000);
+}
+
+#[test]
+fn expired_stop_is_closed_instead_of_triggered() {
+  let mut suite = setup();
+  let expires_at = suite.app.block_info().time.plus_seconds(10);
+  let order_id = suite.submit_ok(
+    CAROL,
+    stop_limit(OrderSide::Sell, "9", 10, "9", TimeInForce::Gtd { expires_at }),
+  );
+  for _ in 0..3 {
+    suite.next_block();
+  }
+
+  suite.submit_ok(BOB, limit_buy(20, "9"));
+  suite.submit_ok(ALICE, market_sell(10, TimeInForce::Ioc));
+
+  assert_eq!(suite.order(CAROL, order_id).status, EXPIRED);
+  assert_eq!(suite.balances(CAROL).base_free, 100);
+  assert_eq!(suite.balances(CAROL).base_locked, 0);
+  assert_eq!(suite.balances(BOB).base_free, 10 + 10);
+}
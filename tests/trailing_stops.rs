@@ -0,0 +1,110 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::Uint128;
+use cw_contract::models::{OrderSide, TimeInForce, TrailingOffset, CANCELED, CREATED, FILLED};
+
+fn setup() -> Suite {
+  let mut suite = Suite::new();
+  suite.deposit_base(ALICE, 1_000);
+  suite.deposit_quote(BOB, 100_000);
+  suite.deposit_base(CAROL, 1_000);
+  trade_at(&mut suite, "1");
+  suite
+}
+
+/// Trade a qty of 10 between Alice and Bob at the given price.
+fn trade_at(
+  suite: &mut Suite,
+  price_str: &str,
+) {
+  suite.submit_ok(ALICE, limit_sell(10, price_str));
+  suite.submit_ok(BOB, limit_buy(10, price_str));
+}
+
+/// Place a trailing stop sell of 10 trailing 0.10 below the highest price.
+fn trailing_sell(suite: &mut Suite) -> u64 {
+  let offset = TrailingOffset::Amount(price("0.1"));
+  suite.submit_ok(CAROL, trailing_stop(OrderSide::Sell, offset, 10, TimeInForce::Ioc))
+}
+
+#[test]
+fn trailing_sell_follows_high_and_triggers_on_pullback() {
+  let mut suite = setup();
+  let order_id = trailing_sell(&mut suite);
+  assert_eq!(suite.order(CAROL, order_id).trigger_price, Some(Uint128::new(90)));
+
+  trade_at(&mut suite, "1.2");
+  let order = suite.order(CAROL, order_id);
+  assert_eq!(order.trailing_price, Some(Uint128::new(120)));
+  assert_eq!(order.trigger_price, Some(Uint128::new(110)));
+
+  // A lower trade doesn't move the trigger price back down.
+  trade_at(&mut suite, "1.15");
+  assert_eq!(suite.order(CAROL, order_id).trigger_price, Some(Uint128::new(110)));
+  assert_eq!(suite.order(CAROL, order_id).status, CREATED);
+
+  // Trading at 1.10 triggers the stop, which sells into the rest of the bid.
+  suite.submit_ok(BOB, limit_buy(20, "1.1"));
+  suite.submit_ok(ALICE, market_sell(10, TimeInForce::Ioc));
+  assert_eq!(suite.order(CAROL, order_id).status, FILLED);
+  assert_eq!(suite.balances(CAROL).base_free, 1_000 - 10);
+  assert_eq!(suite.balances(CAROL).quote_free, 11);
+}
+
+#[test]
+fn trailing_stop_updates_are_capped_per_settlement() {
+  let mut suite = setup();
+  let order_ids: Vec<u64> = (0..12).map(|_| trailing_sell(&mut suite)).collect();
+
+  // The first trade ratchets all but the last two stops.
+  trade_at(&mut suite, "1.2");
+  for (n, order_id) in order_ids.iter().enumerate() {
+    let expected = if n < 10 { 110 } else { 90 };
+    assert_eq!(
+      suite.order(CAROL, *order_id).trigger_price,
+      Some(Uint128::new(expected))
+    );
+  }
+
+  // The stops left behind catch up to the high on the next trade, even though
+  // it trades lower.
+  trade_at(&mut suite, "1.15");
+  for order_id in order_ids.iter() {
+    let order = suite.order(CAROL, *order_id);
+    assert_eq!(order.trailing_price, Some(Uint128::new(120)));
+    assert_eq!(order.trigger_price, Some(Uint128::new(110)));
+  }
+}
+
+#[test]
+fn trailing_stops_catch_up_to_the_highs_traded_since_they_were_placed() {
+  let mut suite = setup();
+  let order_ids: Vec<u64> = (0..25).map(|_| trailing_sell(&mut suite)).collect();
+  trade_at(&mut suite, "1.2");
+  trade_at(&mut suite, "1.15");
+
+  // A stop placed after the high trails from the last price instead.
+  let late_order_id = trailing_sell(&mut suite);
+  trade_at(&mut suite, "1.15");
+
+  for order_id in order_ids.iter() {
+    assert_eq!(suite.order(CAROL, *order_id).trigger_price, Some(Uint128::new(110)));
+  }
+  let late_order = suite.order(CAROL, late_order_id);
+  assert_eq!(late_order.trailing_price, Some(Uint128::new(115)));
+  assert_eq!(late_order.trigger_price, Some(Uint128::new(105)));
+}
+
+#[test]
+fn canceled_trailing_stop_no_longer_trails() {
+  let mut suite = setup();
+  let order_id = trailing_sell(&mut suite);
+  suite.execute(CAROL, &cancel(order_id)).unwrap();
+
+  trade_at(&mut suite, "1.2");
+  let order = suite.order(CAROL, order_id);
+  assert_eq!(order.status, CANCELED);
+  assert_eq!(order.trigger_price, Some(Uint128::new(90)));
+  assert_eq!(suite.balances(CAROL).base_free, 1_000);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::*;
+use cosmwasm_std::{Addr, Uint128};
+use cw_contract::msg::ExecuteMsg;
+
+fn withdraw_quote(
+  amount: Option<u128>,
+  recipient: Option<&str>,
+) -> ExecuteMsg {
+  ExecuteMsg::Withdraw {
+    token: quote(),
+    amount: amount.map(Uint128::from),
+    recipient: recipient.map(Addr::unchecked),
+  }
+}
+
+/// Fill a sell of 10 by Alice at 2 with a buy by Bob, crediting Alice 20 quote
+/// and Bob 10 base.
+fn setup_fill() -> Suite {
+  let mut suite = Suite::new();
+  suite.transfer_base(ALICE, 10);
+  suite.send_base(ALICE, 10, &limit_sell(10, "2")).unwrap();
+  suite.submit_with_funds(BOB, limit_buy(10, "2"), 20).unwrap();
+  suite
+}
+
+#[test]
+fn withdraw_transfers_part_of_the_credited_balance() {
+  let mut suite = setup_fill();
+
+  suite.execute(ALICE, &withdraw_quote(Some(8), None)).unwrap();
+
+  assert_eq!(suite.balances(ALICE).quote_free, 12);
+  assert_eq!(suite.quote_balance(ALICE), QUOTE_SUPPLY + 8);
+}
+
+#[test]
+fn withdraw_defaults_to_the_whole_balance() {
+  let mut suite = setup_fill();
+
+  suite.execute(ALICE, &withdraw_quote(None, Some(CAROL))).unwrap();
+
+  assert_eq!(suite.balances(ALICE).quote_free, 0);
+  assert_eq!(suite.quote_balance(CAROL), QUOTE_SUPPLY + 20);
+}
+
+#[test]
+fn withdraw_cannot_exceed_the_credited_balance() {
+  let mut suite = setup_fill();
+
+  assert_error(
+    suite.execute(ALICE, &withdraw_quote(Some(21), None)),
+    "InsufficientBalance",
+  );
+  assert_error(suite.execute(BOB, &withdraw_quote(None, None)), "InsufficientBalance");
+  suite.execute(ALICE, &withdraw_quote(Some(20), None)).unwrap();
+}
+
+#[test]
+fn withdraw_all_transfers_proceeds_of_a_fill() {
+  let mut suite = setup_fill();
+
+  for account in [ALICE, BOB] {
+    suite
+      .execute(account, &ExecuteMsg::WithdrawAll { recipient: None })
+      .unwrap();
+  }
+
+  assert_eq!(suite.quote_balance(ALICE), QUOTE_SUPPLY + 20);
+  assert_eq!(suite.base_balance(ALICE), 0);
+  assert_eq!(suite.quote_balance(BOB), QUOTE_SUPPLY - 20);
+  assert_eq!(suite.base_balance(BOB), 10);
+  assert_eq!(suite.balances(ALICE), Balances::default());
+  assert_eq!(suite.balances(BOB), Balances::default());
+}